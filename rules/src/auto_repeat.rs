@@ -0,0 +1,115 @@
+// delayed auto shift, pressing a key moves the piece once, and if it stays
+// held it waits for the delay and then keeps moving it every repeat interval
+#[derive(Clone, Copy, Debug)]
+pub struct AutoRepeat {
+    held: bool,
+    // when the next repeated move is due, in milliseconds
+    next_move: u64
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Repeat {
+    // move this many times
+    Moves(u64),
+    // a repeat interval of 0, the piece goes as far as it can
+    Instant
+}
+
+impl Default for AutoRepeat {
+    fn default() -> AutoRepeat {
+        AutoRepeat::new()
+    }
+}
+
+impl AutoRepeat {
+    pub fn new() -> AutoRepeat {
+        AutoRepeat {
+            held: false,
+            next_move: 0
+        }
+    }
+
+    // the key was just pressed, the caller does the first move itself
+    pub fn press(&mut self, time: u64, delay: u64) {
+        self.held = true;
+        self.next_move = time + delay;
+    }
+
+    // stops the repeating until the key is pressed again
+    pub fn cancel(&mut self) {
+        self.held = false;
+    }
+
+    // puts the next repeat off by the time the game was stopped for
+    pub fn shift(&mut self, by: u64) {
+        self.next_move += by;
+    }
+
+    // called every frame with whether the key is still down,
+    // gives back the repeated moves that are due
+    pub fn update(&mut self, down: bool, time: u64, interval: u64) -> Repeat {
+        if !down {
+            self.held = false;
+        }
+
+        if !self.held || time < self.next_move {
+            return Repeat::Moves(0);
+        }
+
+        if interval == 0 {
+            return Repeat::Instant;
+        }
+
+        let moves = (time - self.next_move) / interval + 1;
+        self.next_move += moves * interval;
+        Repeat::Moves(moves)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn waits_for_the_delay_then_repeats() {
+        let mut repeat = AutoRepeat::new();
+        repeat.press(0, 170);
+        assert_eq!(repeat.update(true, 169, 50), Repeat::Moves(0));
+        assert_eq!(repeat.update(true, 170, 50), Repeat::Moves(1));
+        assert_eq!(repeat.update(true, 320, 50), Repeat::Moves(3));
+    }
+
+    #[test]
+    fn letting_go_stops_it() {
+        let mut repeat = AutoRepeat::new();
+        repeat.press(0, 100);
+        assert_eq!(repeat.update(false, 50, 50), Repeat::Moves(0));
+        assert_eq!(repeat.update(true, 500, 50), Repeat::Moves(0));
+    }
+
+    #[test]
+    fn cancelling_stops_it_until_the_next_press() {
+        let mut repeat = AutoRepeat::new();
+        repeat.press(0, 100);
+        repeat.cancel();
+        assert_eq!(repeat.update(true, 500, 50), Repeat::Moves(0));
+        repeat.press(500, 100);
+        assert_eq!(repeat.update(true, 600, 50), Repeat::Moves(1));
+    }
+
+    #[test]
+    fn shifting_puts_the_repeat_off() {
+        let mut repeat = AutoRepeat::new();
+        repeat.press(0, 100);
+        repeat.shift(1000);
+        assert_eq!(repeat.update(true, 1099, 50), Repeat::Moves(0));
+        assert_eq!(repeat.update(true, 1100, 50), Repeat::Moves(1));
+    }
+
+    #[test]
+    fn zero_interval_is_instant() {
+        let mut repeat = AutoRepeat::new();
+        repeat.press(0, 100);
+        assert_eq!(repeat.update(true, 100, 0), Repeat::Instant);
+    }
+}
@@ -0,0 +1,233 @@
+use super::{BOARD_WIDTH, BOARD_HEIGHT, BlockType, BlockRotation, BlockList};
+use super::board::Board;
+use super::scoring::Spin;
+
+type Offsets = [(isize, isize); 4];
+type KickList = [(isize, isize); 5];
+
+// gives you back either the list of all the positions,
+// or none in the case it is out of bounds, or intersecting another
+// block
+pub fn list(board: &Board, x: usize, y: usize,
+    block_type: BlockType, r: BlockRotation) -> Option<BlockList> {
+
+    let offsets = offsets(block_type, r);
+    let mut piece_positions = [(0, 0); 4];
+    for (i, (dx, dy)) in offsets.iter().enumerate() {
+        let block_x = x as isize + dx;
+        let block_y = y as isize + dy;
+        if block_x < 0 || block_y < 0 || block_x >= BOARD_WIDTH as isize ||
+            block_y >= BOARD_HEIGHT as isize {
+            return None;
+        }
+
+        piece_positions[i] = (block_x as usize, block_y as usize);
+    }
+
+    // filter's out positions that overlap something else
+    for (x, y) in piece_positions {
+        if board.is_filled(x, y) {
+            return None;
+        }
+    }
+
+    Some(piece_positions)
+}
+
+// tries to rotate the piece from one state to another, going through the
+// srs kick table in order. gives back the new pivot position of the first
+// kick that fits, along with which test it was
+pub fn rotate(board: &Board, x: usize, y: usize, block_type: BlockType,
+    from: BlockRotation, to: BlockRotation) -> Option<(usize, usize, usize)> {
+
+    for (kick, (dx, dy)) in kicks(block_type, from, to).iter().enumerate() {
+        let kick_x = x as isize + dx;
+        let kick_y = y as isize + dy;
+        if kick_x < 0 || kick_y < 0 {
+            continue;
+        }
+
+        let (kick_x, kick_y) = (kick_x as usize, kick_y as usize);
+        if list(board, kick_x, kick_y, block_type, to).is_some() {
+            return Some((kick_x, kick_y, kick));
+        }
+    }
+
+    None
+}
+
+// the 3 corner rule, checked on a T that was just rotated into place.
+// 3 of the 4 corners around the pivot need to be filled, the walls and floor
+// count as filled. it is a mini unless both corners the T points at are
+// filled, or the rotation needed the last kick test
+pub fn t_spin(board: &Board, x: usize, y: usize, r: BlockRotation,
+    kick: usize) -> Spin {
+
+    use BlockRotation::*;
+    let corners = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+    let filled = |(dx, dy): (isize, isize)| {
+        let corner_x = x as isize + dx;
+        let corner_y = y as isize + dy;
+        if corner_x < 0 || corner_y < 0 || corner_x >= BOARD_WIDTH as isize ||
+            corner_y >= BOARD_HEIGHT as isize {
+            return true;
+        }
+
+        board.is_filled(corner_x as usize, corner_y as usize)
+    };
+
+    if corners.iter().filter(|corner| filled(**corner)).count() < 3 {
+        return Spin::None;
+    }
+
+    let front = match r {
+        Zero => [corners[0], corners[1]],
+        Single => [corners[1], corners[2]],
+        Double => [corners[2], corners[3]],
+        Triple => [corners[3], corners[0]]
+    };
+
+    if (filled(front[0]) && filled(front[1])) || kick == 4 {
+        Spin::Full
+    }
+    else {
+        Spin::Mini
+    }
+}
+
+// positions of each block relative to the srs pivot, with y growing downwards.
+// for J, L, S, T and Z the pivot is the center of their 3x3 box, the I piece
+// rotates inside a 4x4 box where the pivot is the second cell of the second row
+pub fn offsets(block_type: BlockType, r: BlockRotation) -> Offsets {
+    use BlockRotation::*;
+    match block_type {
+        BlockType::T => match r {
+            Zero => [(0, -1), (-1, 0), (0, 0), (1, 0)],
+            Single => [(0, -1), (0, 0), (1, 0), (0, 1)],
+            Double => [(-1, 0), (0, 0), (1, 0), (0, 1)],
+            Triple => [(0, -1), (-1, 0), (0, 0), (0, 1)]
+        },
+        BlockType::J => match r {
+            Zero => [(-1, -1), (-1, 0), (0, 0), (1, 0)],
+            Single => [(0, -1), (1, -1), (0, 0), (0, 1)],
+            Double => [(-1, 0), (0, 0), (1, 0), (1, 1)],
+            Triple => [(0, -1), (0, 0), (-1, 1), (0, 1)]
+        },
+        BlockType::L => match r {
+            Zero => [(1, -1), (-1, 0), (0, 0), (1, 0)],
+            Single => [(0, -1), (0, 0), (0, 1), (1, 1)],
+            Double => [(-1, 0), (0, 0), (1, 0), (-1, 1)],
+            Triple => [(-1, -1), (0, -1), (0, 0), (0, 1)]
+        },
+        BlockType::S => match r {
+            Zero => [(0, -1), (1, -1), (-1, 0), (0, 0)],
+            Single => [(0, -1), (0, 0), (1, 0), (1, 1)],
+            Double => [(0, 0), (1, 0), (-1, 1), (0, 1)],
+            Triple => [(-1, -1), (-1, 0), (0, 0), (0, 1)]
+        },
+        BlockType::Z => match r {
+            Zero => [(-1, -1), (0, -1), (0, 0), (1, 0)],
+            Single => [(1, -1), (0, 0), (1, 0), (0, 1)],
+            Double => [(-1, 0), (0, 0), (0, 1), (1, 1)],
+            Triple => [(0, -1), (-1, 0), (0, 0), (-1, 1)]
+        },
+        BlockType::I => match r {
+            Zero => [(-1, 0), (0, 0), (1, 0), (2, 0)],
+            Single => [(1, -1), (1, 0), (1, 1), (1, 2)],
+            Double => [(-1, 1), (0, 1), (1, 1), (2, 1)],
+            Triple => [(0, -1), (0, 0), (0, 1), (0, 2)]
+        },
+        // the square takes the top right of its 3x3 box, and does not
+        // move when rotated, so it never needs a kick
+        BlockType::Square => [(0, -1), (1, -1), (0, 0), (1, 0)]
+    }
+}
+
+// the srs wall kick tests, the y values are flipped from how they are usually
+// written because our board grows downwards. srs has no kicks for 180
+// rotations, so those are only tried in place
+fn kicks(block_type: BlockType, from: BlockRotation,
+    to: BlockRotation) -> KickList {
+
+    use BlockRotation::*;
+    match block_type {
+        BlockType::Square => [(0, 0); 5],
+        BlockType::I => match (from, to) {
+            (Zero, Single) => [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+            (Single, Zero) => [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+            (Single, Double) => [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+            (Double, Single) => [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+            (Double, Triple) => [(0, 0), (2, 0), (-1, 0), (2, -1), (-1, 2)],
+            (Triple, Double) => [(0, 0), (-2, 0), (1, 0), (-2, 1), (1, -2)],
+            (Triple, Zero) => [(0, 0), (1, 0), (-2, 0), (1, 2), (-2, -1)],
+            (Zero, Triple) => [(0, 0), (-1, 0), (2, 0), (-1, -2), (2, 1)],
+            _ => [(0, 0); 5]
+        },
+        _ => match (from, to) {
+            (Zero, Single) => [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+            (Single, Zero) => [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+            (Single, Double) => [(0, 0), (1, 0), (1, 1), (0, -2), (1, -2)],
+            (Double, Single) => [(0, 0), (-1, 0), (-1, -1), (0, 2), (-1, 2)],
+            (Double, Triple) => [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+            (Triple, Double) => [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+            (Triple, Zero) => [(0, 0), (-1, 0), (-1, 1), (0, -2), (-1, -2)],
+            (Zero, Triple) => [(0, 0), (1, 0), (1, -1), (0, 2), (1, 2)],
+            _ => [(0, 0); 5]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockState;
+    use BlockRotation::*;
+
+    #[test]
+    fn every_rotation_has_four_different_blocks() {
+        for block_type in BlockType::ALL.iter() {
+            for r in [Zero, Single, Double, Triple] {
+                let offsets = offsets(*block_type, r);
+                for i in 0..4 {
+                    assert!(!offsets[i + 1..].contains(&offsets[i]));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn pieces_can_not_leave_the_board() {
+        let board = Board::new();
+        assert!(list(&board, 0, 1, BlockType::T, Zero).is_none());
+        assert!(list(&board, 1, 1, BlockType::T, Zero).is_some());
+        assert!(list(&board, 1, BOARD_HEIGHT, BlockType::T, Zero).is_none());
+    }
+
+    #[test]
+    fn rotating_against_the_wall_kicks_away_from_it() {
+        let board = Board::new();
+        // the I stands up against the left wall, then is turned flat
+        let (x, y, kick) = rotate(&board, 0, 5, BlockType::I, Triple, Zero).unwrap();
+        assert_eq!((x, y, kick), (1, 5, 1));
+    }
+
+    #[test]
+    fn t_spin_needs_three_corners() {
+        let mut board = Board::new();
+        let y = BOARD_HEIGHT - 2;
+        // a T slot, the T sits pointing down between the two sides
+        for x in 0..BOARD_WIDTH {
+            if !(3..=5).contains(&x) {
+                board.set(x, y, BlockState::Garbage);
+            }
+            if x != 4 {
+                board.set(x, y + 1, BlockState::Garbage);
+            }
+        }
+        board.set(3, y - 1, BlockState::Garbage);
+
+        assert_eq!(t_spin(&board, 4, y, Double, 0), Spin::Full);
+        board.set(3, y - 1, BlockState::Empty);
+        assert_eq!(t_spin(&board, 4, y, Double, 0), Spin::None);
+    }
+}
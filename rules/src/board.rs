@@ -0,0 +1,184 @@
+use super::{ BlockState, BOARD_WIDTH, BOARD_HEIGHT };
+
+type Row = [BlockState; BOARD_WIDTH];
+
+// the stack of locked blocks. stored row by row so whole rows
+// can be moved around when lines are cleared
+#[derive(Clone)]
+pub struct Board {
+    rows: [Row; BOARD_HEIGHT],
+    // rows that have to be cleared to win, they move along with the rows
+    marked: [bool; BOARD_HEIGHT]
+}
+
+// which rows a clear removed, by their position before the clear
+#[derive(Clone, Copy, Debug)]
+pub struct ClearedRows {
+    rows: [bool; BOARD_HEIGHT],
+    count: usize
+}
+
+impl ClearedRows {
+    pub fn count(&self) -> usize {
+        self.count
+    }
+
+    pub fn contains(&self, y: usize) -> bool {
+        self.rows[y]
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = usize> + '_ {
+        (0..BOARD_HEIGHT).filter(move |y| self.rows[*y])
+    }
+}
+
+impl Default for Board {
+    fn default() -> Board {
+        Board::new()
+    }
+}
+
+impl Board {
+    pub fn new() -> Board {
+        Board {
+            rows: [[BlockState::Empty; BOARD_WIDTH]; BOARD_HEIGHT],
+            marked: [false; BOARD_HEIGHT]
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> BlockState {
+        self.rows[y][x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, state: BlockState) {
+        self.rows[y][x] = state;
+    }
+
+    pub fn is_filled(&self, x: usize, y: usize) -> bool {
+        match self.rows[y][x] {
+            BlockState::Block(_) | BlockState::Garbage => true,
+            BlockState::Empty => false
+        }
+    }
+
+    pub fn is_row_full(&self, y: usize) -> bool {
+        (0..BOARD_WIDTH).all(|x| self.is_filled(x, y))
+    }
+
+    // nothing left on the board, a perfect clear
+    pub fn is_empty(&self) -> bool {
+        (0..BOARD_HEIGHT).all(|y| (0..BOARD_WIDTH).all(|x| !self.is_filled(x, y)))
+    }
+
+    // one bit for each filled block in the row, from the left
+    pub fn row_mask(&self, y: usize) -> u16 {
+        (0..BOARD_WIDTH).filter(|x| self.is_filled(*x, y))
+            .fold(0, |mask, x| mask | 1 << x)
+    }
+
+    pub fn marked_rows(&self) -> usize {
+        self.marked.iter().filter(|marked| **marked).count()
+    }
+
+    // moves every row up one and fills the bottom with garbage that has
+    // a single hole. gives back false if a block was pushed off the top
+    pub fn push_garbage(&mut self, hole: usize, marked: bool) -> bool {
+        let fits = (0..BOARD_WIDTH).all(|x| !self.is_filled(x, 0));
+
+        self.rows.rotate_left(1);
+        self.marked.rotate_left(1);
+
+        let mut row = [BlockState::Garbage; BOARD_WIDTH];
+        row[hole] = BlockState::Empty;
+        self.rows[BOARD_HEIGHT - 1] = row;
+        self.marked[BOARD_HEIGHT - 1] = marked;
+
+        fits
+    }
+
+    // removes every full row and drops the rows above them down,
+    // the full rows do not need to be next to each other
+    pub fn clear_lines(&mut self) -> ClearedRows {
+        let mut cleared = ClearedRows {
+            rows: [false; BOARD_HEIGHT],
+            count: 0
+        };
+
+        // walk up from the bottom, copying each row that stays
+        // down to the next free spot
+        let mut write = BOARD_HEIGHT;
+        for read in (0..BOARD_HEIGHT).rev() {
+            if self.is_row_full(read) {
+                cleared.rows[read] = true;
+                cleared.count += 1;
+                continue;
+            }
+
+            write -= 1;
+            self.rows[write] = self.rows[read];
+            self.marked[write] = self.marked[read];
+        }
+
+        for y in 0..write {
+            self.rows[y] = [BlockState::Empty; BOARD_WIDTH];
+            self.marked[y] = false;
+        }
+
+        cleared
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::BlockType;
+
+    fn fill_row(board: &mut Board, y: usize) {
+        for x in 0..BOARD_WIDTH {
+            board.set(x, y, BlockState::Block(BlockType::I));
+        }
+    }
+
+    #[test]
+    fn clears_rows_that_are_not_next_to_each_other() {
+        let mut board = Board::new();
+        fill_row(&mut board, BOARD_HEIGHT - 1);
+        board.set(0, BOARD_HEIGHT - 2, BlockState::Garbage);
+        fill_row(&mut board, BOARD_HEIGHT - 3);
+
+        let cleared = board.clear_lines();
+        assert_eq!(cleared.count(), 2);
+        assert!(cleared.contains(BOARD_HEIGHT - 1) && cleared.contains(BOARD_HEIGHT - 3));
+        assert_eq!(board.row_mask(BOARD_HEIGHT - 1), 1);
+        assert_eq!(board.row_mask(BOARD_HEIGHT - 2), 0);
+    }
+
+    #[test]
+    fn garbage_pushes_the_stack_up() {
+        let mut board = Board::new();
+        board.set(3, BOARD_HEIGHT - 1, BlockState::Block(BlockType::T));
+
+        assert!(board.push_garbage(5, true));
+        assert_eq!(board.row_mask(BOARD_HEIGHT - 1), 0b11_1101_1111);
+        assert_eq!(board.row_mask(BOARD_HEIGHT - 2), 1 << 3);
+        assert_eq!(board.marked_rows(), 1);
+    }
+
+    #[test]
+    fn garbage_reports_blocks_pushed_off_the_top() {
+        let mut board = Board::new();
+        board.set(0, 0, BlockState::Garbage);
+        assert!(!board.push_garbage(0, false));
+    }
+
+    #[test]
+    fn marked_rows_go_away_when_cleared() {
+        let mut board = Board::new();
+        board.push_garbage(2, true);
+        board.set(2, BOARD_HEIGHT - 1, BlockState::Block(BlockType::I));
+
+        assert_eq!(board.clear_lines().count(), 1);
+        assert_eq!(board.marked_rows(), 0);
+        assert!(board.is_empty());
+    }
+}
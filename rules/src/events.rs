@@ -0,0 +1,59 @@
+use super::BlockType;
+use super::input::MAX_PRESSES;
+use super::scoring::ClearType;
+
+// something that happened during an update, for the caller to react to
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    Locked(BlockType),
+    // lines were cleared, or a T-spin was made without clearing any
+    Cleared(ClearType),
+    LevelUp(u64),
+    Spawned(BlockType),
+    // the goal of the mode was reached, after this many milliseconds
+    Finished(u64),
+    ToppedOut
+}
+
+// a lock gives at most 4 events, locked, cleared, level up, then the
+// spawn or the end of the game. every press can lock a piece, and so
+// can the update after them
+const EVENTS_PER_LOCK: usize = 4;
+pub const MAX_EVENTS: usize = (MAX_PRESSES + 1) * EVENTS_PER_LOCK;
+
+// the events of one update, in the order they happened
+#[derive(Copy, Clone, Debug)]
+pub struct Events {
+    events: [Event; MAX_EVENTS],
+    count: usize
+}
+
+impl Default for Events {
+    fn default() -> Events {
+        Events::new()
+    }
+}
+
+impl Events {
+    pub fn new() -> Events {
+        Events {
+            events: [Event::ToppedOut; MAX_EVENTS],
+            count: 0
+        }
+    }
+
+    pub fn push(&mut self, event: Event) {
+        if self.count < MAX_EVENTS {
+            self.events[self.count] = event;
+            self.count += 1;
+        }
+    }
+
+    pub fn iter(&self) -> core::slice::Iter<'_, Event> {
+        self.events[..self.count].iter()
+    }
+
+    pub fn contains(&self, event: Event) -> bool {
+        self.iter().any(|happened| *happened == event)
+    }
+}
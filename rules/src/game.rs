@@ -0,0 +1,685 @@
+use super::{ BlockList, BlockRotation, BlockState, BlockType, BOARD_HEIGHT, BOARD_WIDTH,
+    MAX_PREVIEW };
+use super::auto_repeat::{ AutoRepeat, Repeat };
+use super::block_list;
+use super::board::Board;
+use super::events::{ Event, Events };
+use super::input::{ Action, Input };
+use super::levels::{ self, Gravity };
+use super::modes::Mode;
+use super::randomizer::{ Randomizer, Xorshift };
+use super::scoring::{ ClearType, Score, Spin };
+use super::settings::Settings;
+
+// how many times moving on the ground can restart the lock delay
+const MAX_LOCK_RESETS: u32 = 15;
+
+// one player's game. the kernel reads the public fields to draw it
+#[derive(Clone)]
+pub struct GameState {
+    pub board: Board,
+    block_x: usize,
+    block_y: usize,
+    block_rotation: BlockRotation,
+    block_type: BlockType,
+    // when the piece last fell a row, in microseconds so the part
+    // of the row delay left over carries to the next row
+    last_fall: u64,
+    // when the piece touched the ground, none while it is falling
+    lock_started: Option<u64>,
+    lock_resets: u32,
+    // the lowest row the piece has reached, the resets are given back
+    // each time the piece falls further than this
+    lowest_y: usize,
+    // set when the last thing that moved the piece was a rotation,
+    // holding which kick test it used. needed to detect T-spins
+    last_rotation: Option<usize>,
+    left_repeat: AutoRepeat,
+    right_repeat: AutoRepeat,
+    soft_drop_repeat: AutoRepeat,
+    pub lines_cleared: u64,
+    pub level: u64,
+    pub score: Score,
+    // the last clear worth showing, and when it happened
+    pub callout: Option<(ClearType, u64)>,
+    queue: [BlockType; MAX_PREVIEW],
+    pub held: Option<BlockType>,
+    // hold can only be used once until the piece locks
+    pub hold_used: bool,
+    randomizer: Randomizer,
+    // picks the holes in the garbage, kept apart so the pieces
+    // come in the same order with or without garbage
+    garbage_rng: Xorshift,
+    pub settings: Settings,
+    pub mode: Mode,
+    // when the game started, moved forward by however long it was paused
+    pub start_time: u64,
+    paused_at: u64,
+    pub pieces_placed: u64,
+    // how long it took to reach the goal of the mode
+    pub finish_time: Option<u64>,
+    // versus garbage that has not come up yet, and garbage
+    // waiting to be passed on to the other player
+    pub incoming_garbage: u64,
+    sent_garbage: u64,
+    pub over: bool,
+    // what has happened so far in the current update
+    events: Events
+}
+
+impl GameState {
+    pub fn new(settings: Settings, mode: Mode, seed: u64, time: u64) -> GameState {
+        let mut randomizer = Randomizer::new(settings.randomizer, seed);
+        let block_type = randomizer.next();
+        let mut queue = [block_type; MAX_PREVIEW];
+        for piece in queue.iter_mut() {
+            *piece = randomizer.next();
+        }
+
+        // the garbage is seeded differently so its holes do not line up
+        // with the pieces from the randomizer
+        let mut garbage_rng = Xorshift::new(seed.rotate_left(32) ^ 0x5851_f42d_4c95_7f2d);
+        let mut board = Board::new();
+        for _ in 0..mode.garbage_rows().unwrap_or(0) {
+            board.push_garbage(garbage_rng.below(BOARD_WIDTH), true);
+        }
+
+        GameState {
+            board,
+            block_x: 4,
+            block_y: 1,
+            block_rotation: BlockRotation::Zero,
+            block_type,
+            last_fall: time * 1000,
+            lock_started: None,
+            lock_resets: 0,
+            lowest_y: 1,
+            last_rotation: None,
+            left_repeat: AutoRepeat::new(),
+            right_repeat: AutoRepeat::new(),
+            soft_drop_repeat: AutoRepeat::new(),
+            lines_cleared: 0,
+            level: 1,
+            score: Score::new(),
+            callout: None,
+            queue,
+            held: None,
+            hold_used: false,
+            randomizer,
+            garbage_rng,
+            settings,
+            mode,
+            start_time: time,
+            paused_at: time,
+            pieces_placed: 0,
+            finish_time: None,
+            incoming_garbage: 0,
+            sent_garbage: 0,
+            over: false,
+            events: Events::new()
+        }
+    }
+
+    // takes the front of the queue, and fills the back from the randomizer.
+    // the whole queue is always kept full so the sequence does not change
+    // with how many pieces are shown
+    fn next_piece(&mut self) -> BlockType {
+        let next = self.queue[0];
+        self.queue.rotate_left(1);
+        self.queue[MAX_PREVIEW - 1] = self.randomizer.next();
+        next
+    }
+
+    // moves the game forward to the time, handling the presses first,
+    // and gives back what happened along the way
+    pub fn update(&mut self, input: &Input, time: u64) -> Events {
+        for action in input.presses() {
+            handle_action(self, *action, time);
+        }
+        update(self, input, time);
+        core::mem::take(&mut self.events)
+    }
+
+    pub fn pause(&mut self, time: u64) {
+        self.paused_at = time;
+    }
+
+    // the timers did not run while the game was stopped, so the piece
+    // should not fall or lock the moment it comes back
+    pub fn resume(&mut self, time: u64) {
+        let paused = time.saturating_sub(self.paused_at);
+        self.start_time += paused;
+        self.left_repeat.shift(paused);
+        self.right_repeat.shift(paused);
+        self.soft_drop_repeat.shift(paused);
+        self.last_fall = time * 1000;
+        if self.lock_started.is_some() {
+            self.lock_started = Some(time);
+        }
+    }
+
+    // the piece that is falling, none if it spawned on top of the stack
+    pub fn piece(&self) -> Option<(BlockType, BlockList)> {
+        Some((self.block_type, translated_list(self, 0, 0)?))
+    }
+
+    // where the piece would end up if it was hard dropped
+    pub fn ghost(&self) -> Option<BlockList> {
+        let mut drop = 0;
+        while translated_list(self, 0, drop + 1).is_some() {
+            drop += 1;
+        }
+        translated_list(self, 0, drop)
+    }
+
+    pub fn previews(&self) -> &[BlockType] {
+        &self.queue[..self.settings.visible_previews()]
+    }
+
+    pub fn elapsed(&self, time: u64) -> u64 {
+        time.saturating_sub(self.start_time)
+    }
+
+    // the garbage this game sent since the last call, for the other player
+    pub fn take_sent_garbage(&mut self) -> u64 {
+        core::mem::replace(&mut self.sent_garbage, 0)
+    }
+
+    pub fn receive_garbage(&mut self, lines: u64) {
+        self.incoming_garbage += lines;
+    }
+}
+
+// moves the game forward one frame
+fn update(state: &mut GameState, input: &Input, current_time: u64) {
+    // the timed modes can end without a piece locking
+    let elapsed = current_time.saturating_sub(state.start_time);
+    if state.mode.is_finished(state.lines_cleared, elapsed, state.board.marked_rows()) {
+        let limit = state.mode.time_limit().unwrap_or(elapsed);
+        finish(state, elapsed.min(limit));
+        return;
+    }
+
+    handle_held_keys(state, input, current_time);
+
+    // if it was moved with the keyboard,
+    // then it needs to be recalculated to the new position
+    let down_list = translated_list(state, 0, 1);
+    let current_list = match translated_list(state, 0, 0) {
+        Some(list) => list,
+        None => {
+            top_out(state);
+            return;
+        }
+    };
+
+    if state.block_y > state.lowest_y {
+        state.lowest_y = state.block_y;
+        state.lock_resets = 0;
+    }
+
+    match down_list {
+        Some(_) => {
+            state.lock_started = None;
+            apply_gravity(state, current_time);
+        },
+        None => {
+            // gravity does not build up while the piece is on the ground
+            state.last_fall = current_time * 1000;
+            let lock_started = *state.lock_started.get_or_insert(current_time);
+            if current_time.saturating_sub(lock_started) >= state.settings.lock_delay {
+                solidify_piece(state, &current_list, current_time);
+            }
+        }
+    }
+}
+
+// moves the piece down however many rows it should have fallen
+// since the last time it moved
+fn apply_gravity(state: &mut GameState, time: u64) {
+    let now = time * 1000;
+    let rows = match levels::gravity(state.level) {
+        Gravity::TwentyG => {
+            state.last_fall = now;
+            BOARD_HEIGHT as u64
+        },
+        Gravity::RowDelay(delay) => {
+            let elapsed = now.saturating_sub(state.last_fall);
+            if elapsed < delay {
+                return;
+            }
+            let rows = elapsed / delay;
+            state.last_fall += rows * delay;
+            rows
+        }
+    };
+
+    for _ in 0..rows {
+        if translated_list(state, 0, 1).is_none() {
+            break;
+        }
+        state.block_y += 1;
+        state.last_rotation = None;
+    }
+}
+
+// shorthand for a translation that is used a lot
+fn translated_list(state: &GameState, x_trans: isize, 
+    y_trans: isize) -> Option<BlockList> {
+
+    // prevent underflow of unsigned type
+    if (state.block_x as isize) + x_trans < 0 || 
+        (state.block_y as isize) + y_trans < 0 {
+
+        return None;
+    }
+
+    let x = ((state.block_x as isize) + x_trans) as usize;
+    let y = ((state.block_y as isize) + y_trans) as usize;
+    block_list::list(&state.board, x, y, state.block_type, state.block_rotation)
+}
+
+fn handle_held_keys(state: &mut GameState, input: &Input, time: u64) {
+    let settings = state.settings;
+    let left = state.left_repeat.update(input.is_held(Action::MoveLeft),
+        time, settings.arr);
+    shift_piece(state, -1, left, time);
+
+    let right = state.right_repeat.update(input.is_held(Action::MoveRight),
+        time, settings.arr);
+    shift_piece(state, 1, right, time);
+
+    let down = state.soft_drop_repeat.update(input.is_held(Action::SoftDrop),
+        time, settings.soft_drop_rate);
+    soft_drop(state, down);
+}
+
+fn handle_action(state: &mut GameState, action: Action, time: u64) {
+    let settings = state.settings;
+    match action {
+        // the last direction pressed wins, holding both does not
+        // make the piece go back and forth
+        Action::MoveLeft => {
+            state.right_repeat.cancel();
+            state.left_repeat.press(time, settings.das);
+            shift_piece(state, -1, Repeat::Moves(1), time);
+        },
+        Action::MoveRight => {
+            state.left_repeat.cancel();
+            state.right_repeat.press(time, settings.das);
+            shift_piece(state, 1, Repeat::Moves(1), time);
+        },
+        Action::SoftDrop => {
+            state.soft_drop_repeat.press(time, settings.soft_drop_rate);
+            soft_drop(state, Repeat::Moves(1));
+        },
+        Action::HardDrop => {
+            while translated_list(state, 0, 1).is_some() {
+                state.block_y += 1;
+                state.last_rotation = None;
+                state.score.hard_drop(1);
+            }
+            // a piece that spawned on top of the stack has nowhere to lock,
+            // the update will end the game instead
+            if let Some(list) = translated_list(state, 0, 0) {
+                solidify_piece(state, &list, time);
+            }
+        },
+        Action::RotateCW | Action::RotateCCW | Action::Rotate180 => {
+            let rotation = match action {
+                Action::RotateCW => state.block_rotation.right_rotate(),
+                Action::RotateCCW => state.block_rotation.left_rotate(),
+                _ => state.block_rotation.flip()
+            };
+            if rotate_piece(state, rotation) {
+                reset_lock_delay(state, time);
+            }
+        },
+        Action::Hold => hold_piece(state),
+        // pausing stops the updates, so it is up to the caller
+        Action::Pause => {}
+    }
+}
+
+fn soft_drop(state: &mut GameState, repeat: Repeat) {
+    let rows = match repeat {
+        Repeat::Moves(rows) => rows,
+        Repeat::Instant => BOARD_HEIGHT as u64
+    };
+
+    for _ in 0..rows {
+        if translated_list(state, 0, 1).is_none() {
+            break;
+        }
+        state.block_y += 1;
+        state.last_rotation = None;
+        state.score.soft_drop(1);
+    }
+}
+
+// moves the piece sideways for the auto repeat, stopping at the first wall
+fn shift_piece(state: &mut GameState, direction: isize, repeat: Repeat, time: u64) {
+    let moves = match repeat {
+        Repeat::Moves(moves) => moves,
+        Repeat::Instant => BOARD_WIDTH as u64
+    };
+
+    for _ in 0..moves {
+        if translated_list(state, direction, 0).is_none() {
+            break;
+        }
+        state.block_x = (state.block_x as isize + direction) as usize;
+        state.last_rotation = None;
+        reset_lock_delay(state, time);
+    }
+}
+
+// gives back if the rotation was possible
+fn rotate_piece(state: &mut GameState, rotation: BlockRotation) -> bool {
+    if let Some((x, y, kick)) = block_list::rotate(&state.board, state.block_x,
+        state.block_y, state.block_type, state.block_rotation, rotation) {
+
+        state.block_x = x;
+        state.block_y = y;
+        state.block_rotation = rotation;
+        state.last_rotation = Some(kick);
+        return true;
+    }
+
+    false
+}
+
+// moving while on the ground restarts the lock delay,
+// but only so many times before the piece has to fall again
+fn reset_lock_delay(state: &mut GameState, time: u64) {
+    if state.lock_started.is_some() && state.lock_resets < MAX_LOCK_RESETS {
+        state.lock_started = Some(time);
+        state.lock_resets += 1;
+    }
+}
+
+fn hold_piece(state: &mut GameState) {
+    if state.hold_used {
+        return;
+    }
+
+    let block_type = match state.held {
+        Some(held) => held,
+        None => state.next_piece()
+    };
+    state.held = Some(state.block_type);
+    state.hold_used = true;
+    spawn_piece(state, block_type);
+}
+
+fn spawn_piece(state: &mut GameState, block_type: BlockType) {
+    state.block_y = 1; // to avoid overflow
+    state.block_x = 4; 
+    state.block_type = block_type;
+    state.block_rotation = BlockRotation::Zero;
+    state.lock_started = None;
+    state.lock_resets = 0;
+    state.last_rotation = None;
+
+    // at 20g the piece shows up already sitting on the stack
+    if levels::gravity(state.level) == Gravity::TwentyG {
+        while translated_list(state, 0, 1).is_some() {
+            state.block_y += 1;
+        }
+    }
+    state.lowest_y = state.block_y;
+    state.events.push(Event::Spawned(block_type));
+}
+
+fn solidify_piece(state: &mut GameState, list: &BlockList, time: u64) {
+    let spin = match (state.block_type, state.last_rotation) {
+        (BlockType::T, Some(kick)) => block_list::t_spin(&state.board,
+            state.block_x, state.block_y, state.block_rotation, kick),
+        _ => Spin::None
+    };
+
+    for (x, y) in list {
+        state.board.set(*x, *y, BlockState::Block(state.block_type));
+    }
+    state.events.push(Event::Locked(state.block_type));
+
+    let cleared = state.board.clear_lines();
+    let perfect_clear = cleared.count() > 0 && state.board.is_empty();
+
+    // the clear is scored at the level it was made on
+    let clear = ClearType::new(cleared.count(), spin);
+    state.score.lock(clear, state.level);
+
+    // an attack cancels out garbage that has not come up yet before any is sent
+    let attack = state.score.attack(clear, perfect_clear);
+    let cancelled = attack.min(state.incoming_garbage);
+    state.incoming_garbage -= cancelled;
+    state.sent_garbage += attack - cancelled;
+    if clear.callout().is_some() {
+        state.callout = Some((clear, time));
+    }
+    if clear != ClearType::None {
+        state.events.push(Event::Cleared(clear));
+    }
+    state.lines_cleared += clear.lines();
+    if state.mode.has_levels() {
+        let level = levels::level_for(state.lines_cleared);
+        if level > state.level {
+            state.events.push(Event::LevelUp(level));
+        }
+        state.level = level;
+    }
+    state.pieces_placed += 1;
+
+    let garbage_left = state.board.marked_rows();
+    let elapsed = state.elapsed(time);
+    if state.mode.is_finished(state.lines_cleared, elapsed, garbage_left) {
+        finish(state, elapsed);
+        return;
+    }
+
+    // garbage only comes up when the piece did not clear anything,
+    // every row of one attack has its hole in the same column
+    if cleared.count() == 0 && state.incoming_garbage > 0 {
+        let hole = state.garbage_rng.below(BOARD_WIDTH);
+        let mut overflowed = false;
+        for _ in 0..state.incoming_garbage {
+            overflowed |= !state.board.push_garbage(hole, false);
+        }
+        state.incoming_garbage = 0;
+        if overflowed {
+            top_out(state);
+            return;
+        }
+    }
+
+    if let Some(interval) = state.mode.garbage_interval() {
+        if state.pieces_placed.is_multiple_of(interval) {
+            let hole = state.garbage_rng.below(BOARD_WIDTH);
+            if !state.board.push_garbage(hole, false) {
+                top_out(state);
+                return;
+            }
+        }
+    }
+
+    // the next piece is spawned after the lines are cleared,
+    // so it is placed against the new stack
+    let block_type = state.next_piece();
+    spawn_piece(state, block_type);
+    state.hold_used = false;
+}
+
+// the timed modes keep finishing every update until the caller stops,
+// so the event is only given the first time
+fn finish(state: &mut GameState, time_taken: u64) {
+    if state.finish_time.is_none() {
+        state.events.push(Event::Finished(time_taken));
+    }
+    state.finish_time = Some(time_taken);
+}
+
+fn top_out(state: &mut GameState) {
+    if !state.over {
+        state.events.push(Event::ToppedOut);
+    }
+    state.over = true;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(state: &mut GameState, action: Action, time: u64) -> Events {
+        let mut input = Input::new();
+        input.press(action);
+        state.update(&input, time)
+    }
+
+    #[test]
+    fn hard_drop_locks_and_spawns_the_next_piece() {
+        let mut state = GameState::new(Settings::default(), Mode::Endless, 1, 0);
+        let next = state.previews()[0];
+        let ghost = state.ghost().unwrap();
+
+        let first = state.piece().unwrap().0;
+
+        let events = press(&mut state, Action::HardDrop, 0);
+        assert_eq!(state.pieces_placed, 1);
+        assert_eq!(state.piece().unwrap().0, next);
+        assert_eq!(events.iter().copied().collect::<Vec<_>>(),
+            vec![Event::Locked(first), Event::Spawned(next)]);
+        for (x, y) in ghost.iter() {
+            assert!(state.board.is_filled(*x, *y));
+        }
+    }
+
+    #[test]
+    fn the_same_seed_deals_the_same_game() {
+        let a = GameState::new(Settings::default(), Mode::Versus, 99, 0);
+        let b = GameState::new(Settings::default(), Mode::Versus, 99, 0);
+        assert_eq!(a.piece().unwrap().0, b.piece().unwrap().0);
+        assert_eq!(a.previews(), b.previews());
+    }
+
+    #[test]
+    fn hold_can_only_be_used_once_per_piece() {
+        let mut state = GameState::new(Settings::default(), Mode::Endless, 3, 0);
+        let first = state.piece().unwrap().0;
+        let second = state.previews()[0];
+
+        press(&mut state, Action::Hold, 0);
+        assert_eq!(state.held, Some(first));
+        assert_eq!(state.piece().unwrap().0, second);
+
+        press(&mut state, Action::Hold, 0);
+        assert_eq!(state.piece().unwrap().0, second);
+    }
+
+    #[test]
+    fn the_last_direction_pressed_wins() {
+        // both games tap left then hold right, but one keeps left held
+        let mut both = GameState::new(Settings::default(), Mode::Endless, 6, 0);
+        let mut right = both.clone();
+        let mut input = Input::new();
+        input.press(Action::MoveLeft);
+        right.update(&input, 0);
+        input.set_held(Action::MoveLeft, true);
+        both.update(&input, 0);
+
+        input.clear_presses();
+        input.press(Action::MoveRight);
+        input.set_held(Action::MoveRight, true);
+        let mut right_input = Input::new();
+        right_input.press(Action::MoveRight);
+        right_input.set_held(Action::MoveRight, true);
+        for time in (10..400).step_by(10) {
+            both.update(&input, time);
+            right.update(&right_input, time);
+            input.clear_presses();
+            right_input.clear_presses();
+            assert_eq!(both.block_x, right.block_x);
+        }
+    }
+
+    #[test]
+    fn pieces_fall_with_time() {
+        let mut state = GameState::new(Settings::default(), Mode::Endless, 5, 0);
+        let before = state.piece().unwrap().1;
+        state.update(&Input::new(), 1000);
+        let after = state.piece().unwrap().1;
+        assert_eq!(after[0].1, before[0].1 + 1);
+    }
+
+    #[test]
+    fn gravity_keeps_the_rest_of_the_row_delay() {
+        // 7059 microseconds a row, which 1 millisecond frames do not divide
+        let mut state = GameState::new(Settings::default(), Mode::Endless, 5, 0);
+        state.level = 15;
+        let before = state.block_y;
+        for time in 1..=71 {
+            state.update(&Input::new(), time);
+        }
+        assert_eq!(state.block_y, before + 10);
+    }
+
+    #[test]
+    fn held_keys_do_not_repeat_right_after_a_pause() {
+        let mut state = GameState::new(Settings::default(), Mode::Endless, 5, 0);
+        let mut input = Input::new();
+        input.press(Action::MoveLeft);
+        input.set_held(Action::MoveLeft, true);
+        state.update(&input, 0);
+        let x = state.block_x;
+
+        state.pause(10);
+        state.resume(100_000);
+        input.clear_presses();
+        state.update(&input, 100_001);
+        assert_eq!(state.block_x, x);
+    }
+
+    #[test]
+    fn a_clock_going_back_does_not_panic() {
+        let mut state = GameState::new(Settings::default(), Mode::Endless, 5, 1000);
+        state.update(&Input::new(), 500);
+        press(&mut state, Action::HardDrop, 400);
+        state.pause(300);
+        state.resume(200);
+        assert_eq!(state.elapsed(100), 0);
+    }
+
+    #[test]
+    fn ultra_ends_at_the_time_limit() {
+        let mut state = GameState::new(Settings::default(), Mode::Ultra, 5, 0);
+        let events = state.update(&Input::new(), 130_000);
+        assert_eq!(state.finish_time, Some(120_000));
+        assert!(events.contains(Event::Finished(120_000)));
+
+        // it is only given once
+        let events = state.update(&Input::new(), 131_000);
+        assert_eq!(events.iter().count(), 0);
+    }
+
+    #[test]
+    fn topping_out_is_given_once() {
+        let mut state = GameState::new(Settings::default(), Mode::Endless, 4, 0);
+        let mut topped_out = 0;
+        for _ in 0..100 {
+            let events = press(&mut state, Action::HardDrop, 0);
+            topped_out += events.iter().filter(|event| **event == Event::ToppedOut).count();
+        }
+        assert!(state.over);
+        assert_eq!(topped_out, 1);
+    }
+
+    #[test]
+    fn received_garbage_comes_up_after_a_lock() {
+        let mut state = GameState::new(Settings::default(), Mode::Versus, 8, 0);
+        state.receive_garbage(2);
+        press(&mut state, Action::HardDrop, 0);
+        assert_eq!(state.incoming_garbage, 0);
+        assert_eq!(state.board.row_mask(BOARD_HEIGHT - 1).count_ones(), 9);
+        assert_eq!(state.board.row_mask(BOARD_HEIGHT - 2).count_ones(), 9);
+    }
+}
@@ -0,0 +1,82 @@
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Action {
+    MoveLeft, MoveRight, SoftDrop, HardDrop,
+    RotateCW, RotateCCW, Rotate180, Hold, Pause
+}
+
+pub const ACTION_COUNT: usize = 9;
+
+impl Action {
+    pub const ALL: [Action; ACTION_COUNT] = [
+        Action::MoveLeft, Action::MoveRight, Action::SoftDrop, Action::HardDrop,
+        Action::RotateCW, Action::RotateCCW, Action::Rotate180, Action::Hold,
+        Action::Pause
+    ];
+
+    pub fn name(self) -> &'static str {
+        use Action::*;
+        match self {
+            MoveLeft => "move left",
+            MoveRight => "move right",
+            SoftDrop => "soft drop",
+            HardDrop => "hard drop",
+            RotateCW => "rotate right",
+            RotateCCW => "rotate left",
+            Rotate180 => "rotate 180",
+            Hold => "hold",
+            Pause => "pause"
+        }
+    }
+}
+
+// presses past this in one update are dropped
+pub const MAX_PRESSES: usize = 16;
+
+// what the player did since the last update. the presses are handled
+// in order, and the held actions drive the auto repeat
+#[derive(Copy, Clone, Debug)]
+pub struct Input {
+    presses: [Action; MAX_PRESSES],
+    press_count: usize,
+    held: [bool; ACTION_COUNT]
+}
+
+impl Default for Input {
+    fn default() -> Input {
+        Input::new()
+    }
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input {
+            presses: [Action::Pause; MAX_PRESSES],
+            press_count: 0,
+            held: [false; ACTION_COUNT]
+        }
+    }
+
+    pub fn press(&mut self, action: Action) {
+        if self.press_count < MAX_PRESSES {
+            self.presses[self.press_count] = action;
+            self.press_count += 1;
+        }
+    }
+
+    pub fn set_held(&mut self, action: Action, down: bool) {
+        self.held[action as usize] = down;
+    }
+
+    // forgets the presses once they have been handled, the held actions stay
+    pub fn clear_presses(&mut self) {
+        self.press_count = 0;
+    }
+
+    pub fn presses(&self) -> &[Action] {
+        &self.presses[..self.press_count]
+    }
+
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held[action as usize]
+    }
+}
@@ -0,0 +1,33 @@
+const LINES_PER_LEVEL: u64 = 10;
+
+// from this level on pieces fall the whole board in a single frame
+const TWENTY_G_LEVEL: u64 = 20;
+
+// microseconds it takes a piece to fall one row at levels 1 to 19, from the
+// guideline formula (0.8 - (level - 1) * 0.007) ^ (level - 1) seconds
+const GRAVITY_TABLE: [u64; 19] = [
+    1000000, 793000, 617796, 472729, 355197, 262004, 189677, 134735, 93882,
+    64152, 42976, 28218, 18153, 11439, 7059, 4264, 2520, 1457, 824
+];
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Gravity {
+    // microseconds per row
+    RowDelay(u64),
+    // the piece goes straight to the bottom
+    TwentyG
+}
+
+// levels start at 1 and go up every 10 lines
+pub fn level_for(lines: u64) -> u64 {
+    1 + lines / LINES_PER_LEVEL
+}
+
+pub fn gravity(level: u64) -> Gravity {
+    if level >= TWENTY_G_LEVEL {
+        return Gravity::TwentyG;
+    }
+
+    let index = level.max(1) - 1;
+    Gravity::RowDelay(GRAVITY_TABLE[index as usize])
+}
@@ -0,0 +1,72 @@
+// the rules of the game, with nothing that needs the hardware. the kernel
+// feeds it input and a time in milliseconds, and draws what it gives back
+#![cfg_attr(not(test), no_std)]
+
+pub mod board;
+pub mod block_list;
+pub mod randomizer;
+pub mod levels;
+pub mod scoring;
+pub mod auto_repeat;
+pub mod modes;
+pub mod settings;
+pub mod input;
+pub mod events;
+mod game;
+
+pub use game::GameState;
+
+pub const BOARD_WIDTH: usize = 10;
+pub const BOARD_HEIGHT: usize = 22;
+pub const MAX_PREVIEW: usize = 6;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockState {
+    Block(BlockType), Garbage, Empty
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockType {
+    I, Z, S, T, Square, J, L
+}
+
+impl BlockType {
+    pub const ALL: [BlockType; 7] = [
+        BlockType::I, BlockType::Z, BlockType::S, BlockType::T,
+        BlockType::Square, BlockType::J, BlockType::L
+    ];
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlockRotation {
+    Zero, Single, Double, Triple
+}
+
+impl BlockRotation {
+
+    pub fn right_rotate(self: BlockRotation) -> Self {
+        use BlockRotation::*;
+        match self {
+            Zero => Single,
+            Single => Double,
+            Double => Triple,
+            Triple => Zero
+        }
+    }
+
+    pub fn flip(self: BlockRotation) -> Self {
+        self.right_rotate().right_rotate()
+    }
+
+    pub fn left_rotate(self: BlockRotation) -> Self {
+        use BlockRotation::*;
+        match self {
+            Zero => Triple,
+            Single => Zero,
+            Double => Single,
+            Triple => Double
+        }
+    }
+}
+
+pub type BlockList = [(usize, usize); 4];
@@ -0,0 +1,160 @@
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Mode {
+    // the original game, it goes until the stack tops out
+    Endless,
+    // clear 40 lines as fast as possible
+    Sprint,
+    // as many points as possible in 2 minutes
+    Ultra,
+    // 150 lines, going up a level every 10
+    Marathon,
+    // dig through the garbage the board starts with, while more comes up
+    Dig,
+    // two players on one keyboard sending garbage to each other
+    Versus,
+    // versus against another machine on the serial port
+    Link
+}
+
+impl Mode {
+    pub const ALL: [Mode; 7] = [
+        Mode::Endless, Mode::Sprint, Mode::Ultra, Mode::Marathon, Mode::Dig,
+        Mode::Versus, Mode::Link
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Mode::Endless => "endless",
+            Mode::Sprint => "sprint 40 lines",
+            Mode::Ultra => "ultra 2 minutes",
+            Mode::Marathon => "marathon 150 lines",
+            Mode::Dig => "dig 10 rows",
+            Mode::Versus => "2 player versus",
+            Mode::Link => "versus over serial"
+        }
+    }
+
+    pub fn players(self) -> usize {
+        match self {
+            Mode::Versus => 2,
+            _ => 1
+        }
+    }
+
+    // the mode ends once this many lines are cleared
+    pub fn line_goal(self) -> Option<u64> {
+        match self {
+            Mode::Sprint => Some(40),
+            Mode::Marathon => Some(150),
+            Mode::Endless | Mode::Ultra | Mode::Dig | Mode::Versus | Mode::Link => None
+        }
+    }
+
+    // the mode ends after this many milliseconds
+    pub fn time_limit(self) -> Option<u64> {
+        match self {
+            Mode::Ultra => Some(120_000),
+            _ => None
+        }
+    }
+
+    // how many rows of garbage the board starts with, the mode
+    // ends once they are all cleared
+    pub fn garbage_rows(self) -> Option<usize> {
+        match self {
+            Mode::Dig => Some(10),
+            _ => None
+        }
+    }
+
+    // another row of garbage comes up after this many pieces
+    pub fn garbage_interval(self) -> Option<u64> {
+        match self {
+            Mode::Dig => Some(8),
+            _ => None
+        }
+    }
+
+    // the other modes are played at a fixed speed
+    pub fn has_levels(self) -> bool {
+        match self {
+            Mode::Endless | Mode::Marathon => true,
+            Mode::Sprint | Mode::Ultra | Mode::Dig | Mode::Versus | Mode::Link => false
+        }
+    }
+
+    // whether a finished game goes in the best times, otherwise it is the score that counts
+    pub fn ranked_by_time(self) -> bool {
+        matches!(self, Mode::Sprint | Mode::Dig)
+    }
+
+    pub fn is_finished(self, lines: u64, elapsed: u64, garbage_left: usize) -> bool {
+        self.line_goal().is_some_and(|goal| lines >= goal) ||
+            self.time_limit().is_some_and(|limit| elapsed >= limit) ||
+            (self.garbage_rows().is_some() && garbage_left == 0)
+    }
+}
+
+pub const BEST_COUNT: usize = 5;
+
+// the fastest times since boot for one of the modes ranked by time, fastest first
+#[derive(Copy, Clone, Debug)]
+pub struct BestTimes {
+    pub times: [Option<u64>; BEST_COUNT]
+}
+
+impl Default for BestTimes {
+    fn default() -> BestTimes {
+        BestTimes::new()
+    }
+}
+
+impl BestTimes {
+    pub fn new() -> BestTimes {
+        BestTimes {
+            times: [None; BEST_COUNT]
+        }
+    }
+
+    // gives back the place the time took, if it was fast enough to be kept
+    pub fn insert(&mut self, time: u64) -> Option<usize> {
+        let place = self.times.iter()
+            .position(|best| best.is_none_or(|best| time < best))?;
+
+        for i in (place + 1..BEST_COUNT).rev() {
+            self.times[i] = self.times[i - 1];
+        }
+        self.times[place] = Some(time);
+        Some(place)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn best_times_stay_sorted() {
+        let mut best = BestTimes::new();
+        assert_eq!(best.insert(500), Some(0));
+        assert_eq!(best.insert(300), Some(0));
+        assert_eq!(best.insert(400), Some(1));
+        assert_eq!(best.times[..3], [Some(300), Some(400), Some(500)]);
+
+        for time in 100..104 {
+            best.insert(time);
+        }
+        assert_eq!(best.insert(1000), None);
+        assert_eq!(best.times[BEST_COUNT - 1], Some(300));
+    }
+
+    #[test]
+    fn modes_finish_on_their_goals() {
+        assert!(!Mode::Sprint.is_finished(39, 0, 0));
+        assert!(Mode::Sprint.is_finished(40, 0, 0));
+        assert!(Mode::Ultra.is_finished(0, 120_000, 0));
+        assert!(!Mode::Dig.is_finished(0, 0, 1));
+        assert!(Mode::Dig.is_finished(0, 0, 0));
+        assert!(!Mode::Endless.is_finished(1000, 1_000_000, 0));
+    }
+}
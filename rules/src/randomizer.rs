@@ -0,0 +1,150 @@
+use super::BlockType;
+
+const PIECE_COUNT: usize = 7;
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum RandomizerKind {
+    // every piece once in a shuffled bag of 7, the guideline behaviour
+    Bag,
+    // each piece picked on its own, with nothing stopping long droughts
+    Random,
+    // the nes rule, if the same piece comes up twice in a row
+    // it gets rolled one more time
+    Reroll
+}
+
+impl RandomizerKind {
+    pub const ALL: [RandomizerKind; 3] = [
+        RandomizerKind::Bag, RandomizerKind::Random, RandomizerKind::Reroll
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RandomizerKind::Bag => "7 bag",
+            RandomizerKind::Random => "random",
+            RandomizerKind::Reroll => "reroll"
+        }
+    }
+}
+
+// xorshift64, small and fast enough to call every piece,
+// and the sequence only depends on the seed
+#[derive(Clone, Debug)]
+pub struct Xorshift {
+    state: u64
+}
+
+impl Xorshift {
+    pub fn new(seed: u64) -> Xorshift {
+        // zero is the one state xorshift can never leave
+        let state = if seed == 0 { 0x9e37_79b9_7f4a_7c15 } else { seed };
+        Xorshift { state }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x << 13;
+        x ^= x >> 7;
+        x ^= x << 17;
+        self.state = x;
+        x
+    }
+
+    // a number in 0..bound
+    pub fn below(&mut self, bound: usize) -> usize {
+        (self.next_u64() % bound as u64) as usize
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Randomizer {
+    kind: RandomizerKind,
+    rng: Xorshift,
+    bag: [BlockType; PIECE_COUNT],
+    bag_left: usize,
+    last: Option<BlockType>
+}
+
+impl Randomizer {
+    pub fn new(kind: RandomizerKind, seed: u64) -> Randomizer {
+        Randomizer {
+            kind,
+            rng: Xorshift::new(seed),
+            bag: BlockType::ALL,
+            bag_left: 0,
+            last: None
+        }
+    }
+
+    // never runs out, so it is not an iterator
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> BlockType {
+        let block_type = match self.kind {
+            RandomizerKind::Bag => self.next_from_bag(),
+            RandomizerKind::Random => self.roll(),
+            RandomizerKind::Reroll => {
+                let first = self.roll();
+                match self.last {
+                    Some(last) if last == first => self.roll(),
+                    _ => first
+                }
+            }
+        };
+
+        self.last = Some(block_type);
+        block_type
+    }
+
+    fn roll(&mut self) -> BlockType {
+        BlockType::ALL[self.rng.below(PIECE_COUNT)]
+    }
+
+    fn next_from_bag(&mut self) -> BlockType {
+        if self.bag_left == 0 {
+            self.bag = BlockType::ALL;
+            // fisher yates shuffle
+            for i in (1..PIECE_COUNT).rev() {
+                let j = self.rng.below(i + 1);
+                self.bag.swap(i, j);
+            }
+            self.bag_left = PIECE_COUNT;
+        }
+
+        self.bag_left -= 1;
+        self.bag[self.bag_left]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bag_deals_every_piece_once_per_seven() {
+        let mut randomizer = Randomizer::new(RandomizerKind::Bag, 42);
+        for _ in 0..10 {
+            let mut seen = [false; PIECE_COUNT];
+            for _ in 0..PIECE_COUNT {
+                let block_type = randomizer.next();
+                let i = BlockType::ALL.iter().position(|b| *b == block_type).unwrap();
+                assert!(!seen[i]);
+                seen[i] = true;
+            }
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_pieces() {
+        let mut a = Randomizer::new(RandomizerKind::Random, 7);
+        let mut b = Randomizer::new(RandomizerKind::Random, 7);
+        for _ in 0..50 {
+            assert_eq!(a.next(), b.next());
+        }
+    }
+
+    #[test]
+    fn zero_seed_still_moves() {
+        let mut rng = Xorshift::new(0);
+        assert_ne!(rng.next_u64(), rng.next_u64());
+    }
+}
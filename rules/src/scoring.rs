@@ -0,0 +1,239 @@
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Spin {
+    None, Mini, Full
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ClearType {
+    None, Single, Double, Triple, Tetris,
+    MiniTSpin, MiniTSpinSingle, MiniTSpinDouble,
+    TSpin, TSpinSingle, TSpinDouble, TSpinTriple
+}
+
+impl ClearType {
+    pub fn new(lines: usize, spin: Spin) -> ClearType {
+        use ClearType::*;
+        match (spin, lines) {
+            (Spin::None, 0) => None,
+            (Spin::None, 1) => Single,
+            (Spin::None, 2) => Double,
+            (Spin::None, 3) => Triple,
+            (Spin::None, _) => Tetris,
+            (Spin::Mini, 0) => MiniTSpin,
+            (Spin::Mini, 1) => MiniTSpinSingle,
+            (Spin::Mini, _) => MiniTSpinDouble,
+            (Spin::Full, 0) => TSpin,
+            (Spin::Full, 1) => TSpinSingle,
+            (Spin::Full, 2) => TSpinDouble,
+            (Spin::Full, _) => TSpinTriple
+        }
+    }
+
+    pub fn lines(self) -> u64 {
+        use ClearType::*;
+        match self {
+            None | MiniTSpin | TSpin => 0,
+            Single | MiniTSpinSingle | TSpinSingle => 1,
+            Double | MiniTSpinDouble | TSpinDouble => 2,
+            Triple | TSpinTriple => 3,
+            Tetris => 4
+        }
+    }
+
+    // points before the level multiplier and bonuses
+    fn base_points(self) -> u64 {
+        use ClearType::*;
+        match self {
+            None => 0,
+            Single => 100,
+            Double => 300,
+            Triple => 500,
+            Tetris => 800,
+            MiniTSpin => 100,
+            MiniTSpinSingle => 200,
+            MiniTSpinDouble => 400,
+            TSpin => 400,
+            TSpinSingle => 800,
+            TSpinDouble => 1200,
+            TSpinTriple => 1600
+        }
+    }
+
+    // the clears that keep a back to back chain going
+    fn is_difficult(self) -> bool {
+        use ClearType::*;
+        matches!(self, Tetris | MiniTSpinSingle | MiniTSpinDouble |
+            TSpinSingle | TSpinDouble | TSpinTriple)
+    }
+
+    // lines of garbage sent in versus, before the bonuses
+    fn base_garbage(self) -> u64 {
+        use ClearType::*;
+        match self {
+            None | MiniTSpin | TSpin | Single | MiniTSpinSingle => 0,
+            Double | MiniTSpinDouble => 1,
+            Triple | TSpinSingle => 2,
+            Tetris | TSpinDouble => 4,
+            TSpinTriple => 6
+        }
+    }
+
+    // the text shown on screen for the clears worth pointing out
+    pub fn callout(self) -> Option<&'static str> {
+        use ClearType::*;
+        match self {
+            Tetris => Some("TETRIS"),
+            MiniTSpin => Some("MINI T-SPIN"),
+            MiniTSpinSingle => Some("MINI T-SPIN SINGLE"),
+            MiniTSpinDouble => Some("MINI T-SPIN DOUBLE"),
+            TSpin => Some("T-SPIN"),
+            TSpinSingle => Some("T-SPIN SINGLE"),
+            TSpinDouble => Some("T-SPIN DOUBLE"),
+            TSpinTriple => Some("T-SPIN TRIPLE"),
+            _ => Option::None
+        }
+    }
+}
+
+const COMBO_POINTS: u64 = 50;
+const BACK_TO_BACK_GARBAGE: u64 = 1;
+const PERFECT_CLEAR_GARBAGE: u64 = 10;
+// extra garbage for the combo, anything longer than this gets the last one
+const COMBO_GARBAGE: [u64; 12] = [0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 4, 5];
+const SOFT_DROP_POINTS: u64 = 1;
+const HARD_DROP_POINTS: u64 = 2;
+
+#[derive(Clone, Debug)]
+pub struct Score {
+    pub points: u64,
+    // how many pieces in a row have cleared lines, minus the first one.
+    // none when the last piece did not clear anything
+    pub combo: Option<u64>,
+    back_to_back: bool,
+    // the last clear continued a back to back chain
+    chained: bool
+}
+
+impl Default for Score {
+    fn default() -> Score {
+        Score::new()
+    }
+}
+
+impl Score {
+    pub fn new() -> Score {
+        Score {
+            points: 0,
+            combo: None,
+            back_to_back: false,
+            chained: false
+        }
+    }
+
+    pub fn soft_drop(&mut self, rows: u64) {
+        self.points += rows * SOFT_DROP_POINTS;
+    }
+
+    pub fn hard_drop(&mut self, rows: u64) {
+        self.points += rows * HARD_DROP_POINTS;
+    }
+
+    // scores a piece locking, with the level it was placed at.
+    // gives back how many points it was worth
+    pub fn lock(&mut self, clear: ClearType, level: u64) -> u64 {
+        let mut points = clear.base_points() * level;
+        self.chained = false;
+
+        if clear.lines() == 0 {
+            self.combo = None;
+        }
+        else {
+            if clear.is_difficult() {
+                if self.back_to_back {
+                    points = points * 3 / 2;
+                    self.chained = true;
+                }
+                self.back_to_back = true;
+            }
+            else {
+                self.back_to_back = false;
+            }
+
+            let combo = self.combo.map_or(0, |combo| combo + 1);
+            points += COMBO_POINTS * combo * level;
+            self.combo = Some(combo);
+        }
+
+        self.points += points;
+        points
+    }
+
+    // the garbage sent by the piece that was just scored with lock
+    pub fn attack(&self, clear: ClearType, perfect_clear: bool) -> u64 {
+        if clear.lines() == 0 {
+            return 0;
+        }
+
+        let mut lines = clear.base_garbage();
+        if self.chained {
+            lines += BACK_TO_BACK_GARBAGE;
+        }
+        if let Some(combo) = self.combo {
+            let index = (combo as usize).min(COMBO_GARBAGE.len() - 1);
+            lines += COMBO_GARBAGE[index];
+        }
+        if perfect_clear {
+            lines += PERFECT_CLEAR_GARBAGE;
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn points_scale_with_level() {
+        let mut score = Score::new();
+        assert_eq!(score.lock(ClearType::Tetris, 3), 2400);
+        assert_eq!(score.points, 2400);
+    }
+
+    #[test]
+    fn back_to_back_is_worth_half_again() {
+        let mut score = Score::new();
+        score.lock(ClearType::Tetris, 1);
+        score.lock(ClearType::None, 1);
+        assert_eq!(score.lock(ClearType::TSpinDouble, 1), 1800);
+
+        // a single breaks the chain
+        score.lock(ClearType::Single, 1);
+        score.lock(ClearType::None, 1);
+        assert_eq!(score.lock(ClearType::Tetris, 1), 800);
+    }
+
+    #[test]
+    fn combos_add_points_and_garbage() {
+        let mut score = Score::new();
+        score.lock(ClearType::Single, 1);
+        score.lock(ClearType::Single, 1);
+        assert_eq!(score.lock(ClearType::Single, 1), 100 + COMBO_POINTS * 2);
+        assert_eq!(score.combo, Some(2));
+        assert_eq!(score.attack(ClearType::Single, false), 1);
+
+        score.lock(ClearType::None, 1);
+        assert_eq!(score.combo, None);
+    }
+
+    #[test]
+    fn attack_bonuses() {
+        let mut score = Score::new();
+        score.lock(ClearType::Tetris, 1);
+        assert_eq!(score.attack(ClearType::Tetris, false), 4);
+        score.lock(ClearType::None, 1);
+        score.lock(ClearType::Tetris, 1);
+        assert_eq!(score.attack(ClearType::Tetris, true), 4 + 1 + PERFECT_CLEAR_GARBAGE);
+        assert_eq!(score.attack(ClearType::None, true), 0);
+    }
+}
@@ -0,0 +1,41 @@
+use super::MAX_PREVIEW;
+use super::randomizer::RandomizerKind;
+
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    pub randomizer: RandomizerKind,
+    // how many upcoming pieces are drawn, from 1 to 6
+    pub preview_count: usize,
+    // shows where the piece will land on a hard drop
+    pub ghost: bool,
+    // milliseconds a piece can sit on the ground before it locks
+    pub lock_delay: u64,
+    // delayed auto shift, how long left or right is held
+    // before it starts repeating, in milliseconds
+    pub das: u64,
+    // auto repeat rate, milliseconds between each repeated move.
+    // 0 moves the piece straight to the wall
+    pub arr: u64,
+    // milliseconds per row while soft dropping, 0 drops to the floor
+    pub soft_drop_rate: u64
+}
+
+impl Default for Settings {
+    fn default() -> Settings {
+        Settings {
+            randomizer: RandomizerKind::Bag,
+            preview_count: 5,
+            ghost: true,
+            lock_delay: 500,
+            das: 170,
+            arr: 50,
+            soft_drop_rate: 30
+        }
+    }
+}
+
+impl Settings {
+    pub fn visible_previews(&self) -> usize {
+        self.preview_count.clamp(1, MAX_PREVIEW)
+    }
+}
@@ -1,244 +1,429 @@
-use crate::kernel::interrupts::{ self, Key, KeyboardState };
-use crate::kernel::graphics::{ self, Color, TTYFrame, TTYBounds };
+// the kernel side of the game. the rules live in the tetris-rules crate,
+// this gathers the keyboard into input for them and draws the result
+use crate::kernel::keyboard::{ KeyCode, KeyEvent };
+use crate::kernel::graphics::TTYFrame;
+use crate::kernel::{ entropy, serial };
+use crate::kernel::time::{ Duration, Instant };
+use crate::{ debug, info, warn };
 use lazy_static::lazy_static;
 use spin::Mutex;
+use tetris_rules::GameState;
+use tetris_rules::events::Event;
+use tetris_rules::input::{ Action, Input };
+use tetris_rules::modes::{ BestTimes, Mode };
+use tetris_rules::settings::Settings;
 
 mod display;
-mod block_list;
-
-const BOARD_WIDTH: usize = 10;
-const BOARD_HEIGHT: usize = 22;
-
-#[derive(Copy, Clone, Debug)]
-pub enum BlockState {
-    Block(Color), Empty
-}
-
-#[derive(Copy, Clone, Debug)]
-pub enum BlockType {
-    I, Z, S, T, Square, J, L
+mod controls;
+mod menu;
+mod link;
+mod settings;
+
+use controls::{ Bindings, ControlsMenu };
+use display::{ FinishStats, Layout };
+use link::{ Link, LinkEvent, LinkResult };
+use menu::Screen;
+use settings::SettingsMenu;
+
+const MAX_PLAYERS: usize = 2;
+// how long a clear like a T-spin double stays on screen
+const CALLOUT_TIME: Duration = Duration::from_millis(1500);
+
+// one person at the keyboard, with the keys they play with
+struct Player {
+    state: GameState,
+    bindings: Bindings,
+    input: Input
 }
 
-impl BlockType {
-    fn color(self) -> Color {
-        use BlockType::*;
-        match self {
-            I => Color::Cyan,
-            Z => Color::Red,
-            S => Color::Blue,
-            T => Color::Magenta,
-            Square => Color::Yellow,
-            J => Color::Green,
-            L => Color::Pink
+impl Player {
+    fn new(state: GameState, bindings: Bindings) -> Player {
+        Player {
+            state,
+            bindings,
+            input: Input::new()
         }
     }
-}
 
-impl BlockType {
-    fn random(time: u64) -> BlockType {
-        use BlockType::*;
-        let result = time % 7;
-        match result {
-            0 => I,
-            1 => Z,
-            2 => S,
-            3 => T,
-            4 => Square,
-            5 => J,
-            6 => L,
-            _ => panic!("Not all randoms handled")
+    // the presses were already added as the keys came in,
+    // the held actions are read from the keyboard right before the update
+    fn update(&mut self, now: Instant) {
+        for action in Action::ALL.iter() {
+            self.input.set_held(*action, self.bindings.is_down(*action));
         }
-    }
-}
 
-#[derive(Copy, Clone, Debug)]
-pub enum BlockRotation {
-    Zero, Single, Double, Triple
-}
-
-impl BlockRotation {
-
-    fn right_rotate(self: BlockRotation) -> Self {
-        use BlockRotation::*;
-        match self {
-            Zero => Single,
-            Single => Double,
-            Double => Triple,
-            Triple => Zero
+        let events = self.state.update(&self.input, now.as_millis());
+        self.input.clear_presses();
+        for event in events.iter() {
+            log_event(&self.state, *event);
         }
     }
 }
 
-type Board = [[BlockState; BOARD_HEIGHT]; BOARD_WIDTH];
-type BlockList = [(usize, usize); 4];
-
-pub struct GameState {
-    board: [[BlockState; BOARD_HEIGHT]; BOARD_WIDTH],
-    block_x: usize,
-    block_y: usize,
-    block_rotation: BlockRotation,
-    block_type: BlockType,
-    time_of_last_move: u64,
-    last_keyboard: KeyboardState,
-    lines_cleared: u64,
-    over: bool
+fn log_event(state: &GameState, event: Event) {
+    match event {
+        Event::Locked(block_type) => debug!("{:?} locked", block_type),
+        Event::Cleared(clear) => {
+            let callout = clear.callout();
+            info!("cleared {} lines{}{}", clear.lines(),
+                if callout.is_some() { " with a " } else { "" }, callout.unwrap_or(""));
+        },
+        Event::LevelUp(level) => info!("reached level {}", level),
+        Event::Spawned(block_type) => debug!("spawned {:?}", block_type),
+        Event::Finished(time) => info!("finished {} in {}ms", state.mode.name(), time),
+        Event::ToppedOut => {
+            info!("topped out with {} points and {} lines", state.score.points,
+                state.lines_cleared);
+        }
+    }
 }
 
 lazy_static! {
-    static ref STATE: Mutex<GameState> = Mutex::new(GameState {
-        board: [[BlockState::Empty; BOARD_HEIGHT]; BOARD_WIDTH],
-        block_x: 4,
-        block_y: 1,
-        block_rotation: BlockRotation::Zero,
-        block_type: BlockType::Square,
-        time_of_last_move: 0,
-        last_keyboard: KeyboardState::blank(),
-        lines_cleared: 0,
-        over: false
-    });
+    // every player's game, only the first is used outside of versus.
+    // they are replaced as soon as a mode is picked
+    static ref PLAYERS: Mutex<[Player; MAX_PLAYERS]> = Mutex::new([
+        Player::new(GameState::new(Settings::default(), Mode::Endless, 1, 0),
+            Bindings::default()),
+        Player::new(GameState::new(Settings::default(), Mode::Endless, 1, 0),
+            Bindings::default())
+    ]);
+    // what each new game starts with, the settings menu changes these
+    static ref SETTINGS: Mutex<Settings> = Mutex::new(settings::load().unwrap_or_default());
+    // the keys for a one player game, the controls menu changes these
+    static ref BINDINGS: Mutex<Bindings> = Mutex::new(Bindings::load().unwrap_or_default());
+    static ref LINK: Mutex<Link> = Mutex::new(Link::new());
+    static ref SCREEN: Mutex<Screen> = Mutex::new(Screen::title());
+    // one table for each mode, in the order of Mode::ALL
+    static ref BEST_TIMES: Mutex<[BestTimes; Mode::ALL.len()]> =
+        Mutex::new([BestTimes::new(); Mode::ALL.len()]);
 }
 
-pub fn run(current_time: u64, keyboard: KeyboardState, frame: &mut TTYFrame) {
-    let mut state = STATE.lock();
-
-    if state.over {
-        frame.print_end_screen(state.lines_cleared);
-        return;
+pub fn run(now: Instant, events: impl Iterator<Item = KeyEvent>,
+    frame: &mut TTYFrame) {
+
+    let mut screen = SCREEN.lock();
+    let mut players = PLAYERS.lock();
+    let mut settings = SETTINGS.lock();
+    let mut bindings = BINDINGS.lock();
+    let mut link = LINK.lock();
+
+    // the keyboard repeating a key is ignored,
+    // held keys are repeated from the timer instead
+    for event in events {
+        if event.pressed && !event.repeat {
+            *screen = handle_key_press(*screen, &mut players, &mut settings, &mut bindings,
+                &mut link, event.key, now);
+        }
     }
 
-    handle_keyboard(&mut state, &keyboard, current_time);
-
-    // if it was moved with the keyboard,
-    // then it needs to be recalculated to the new position
-    let down_list = translated_list(&state, 0, 1);
-    let current_list = match translated_list(&state, 0, 0) {
-        Some(list) => list,
-        None => {
-            state.over = true;
+    if let Screen::Playing = *screen {
+        if players[0].state.mode.players() > 1 {
+            *screen = run_versus(&mut players, now, frame);
             return;
         }
-    };
+        if players[0].state.mode == Mode::Link {
+            *screen = run_link(&mut players[0], &mut link, now, frame);
+            return;
+        }
+    }
 
-    if current_time - state.time_of_last_move > 4 {
-        match down_list {
-            Some(_) => state.block_y += 1,
-            None => solidify_piece(&mut state, &current_list, current_time / 4),
+    match *screen {
+        Screen::Title(menu) => frame.render_title(&menu),
+        Screen::Paused(menu) => frame.render_pause(&menu),
+        Screen::Settings(menu) => frame.render_settings(&menu, &settings),
+        Screen::Controls { menu, .. } => frame.render_controls(&menu, &bindings),
+        Screen::GameOver => {
+            let state = &players[0].state;
+            frame.print_end_screen(state.score.points, state.lines_cleared);
+        },
+        Screen::Finished { place } => {
+            let state = &players[0].state;
+            let best_times = BEST_TIMES.lock();
+            let stats = FinishStats {
+                time: state.finish_time.unwrap_or(0),
+                points: state.score.points,
+                lines: state.lines_cleared,
+                pieces: state.pieces_placed
+            };
+            frame.render_finish(state.mode, &stats, &best_times[state.mode as usize], place);
+        },
+        Screen::Connecting => {
+            while let Some(event) = link.poll(now) {
+                if let LinkEvent::Start(seed) = event {
+                    info!("linked with the other machine");
+                    restart(&mut players, &settings, &bindings, Mode::Link, seed, now);
+                    *screen = Screen::Playing;
+                    return;
+                }
+            }
+            frame.render_connecting(serial::link_present());
+        },
+        Screen::LinkOver { result } => {
+            frame.render_link_over(result, players[0].state.score.points);
+        },
+        Screen::VersusOver { winner } => {
+            let scores = [players[0].state.score.points, players[1].state.score.points];
+            frame.render_versus_over(winner, scores);
+        },
+        Screen::Playing => {
+            players[0].update(now);
+            let state = &players[0].state;
+            if let Some(finish_time) = state.finish_time {
+                let place = match state.mode.ranked_by_time() {
+                    true => BEST_TIMES.lock()[state.mode as usize].insert(finish_time),
+                    false => None
+                };
+                *screen = Screen::Finished { place };
+                return;
+            }
+            if state.over {
+                *screen = Screen::GameOver;
+                return;
+            }
+            render(state, &Layout::single(), now, frame);
         }
+    }
+}
+
+// both players move forward together, and the game ends when either tops out
+fn run_versus(players: &mut [Player; MAX_PLAYERS], now: Instant,
+    frame: &mut TTYFrame) -> Screen {
 
-        state.time_of_last_move = current_time;
+    for player in players.iter_mut() {
+        player.update(now);
     }
-  
-    frame.set_write_bounds(TTYBounds {
-        y: 1, x: 30, end_x: graphics::WIDTH - 1,
-        end_y: graphics::HEIGHT - 1
-    });
 
-    //frame.print_keyboard_state(current_time, state.time_of_last_move, &keyboard);
-    frame.print_score(state.lines_cleared);
-    frame.render_shape(state.block_type.color(), &current_list);
-    frame.render_stale(&state.board);
-    frame.render_outline();
+    // the garbage each player sent goes to the other. it was already
+    // used to cancel their own incoming garbage when it was sent
+    let sent = [players[0].state.take_sent_garbage(), players[1].state.take_sent_garbage()];
+    for (i, lines) in sent.iter().enumerate() {
+        if *lines > 0 {
+            debug!("player {} sent {} lines of garbage", i + 1, lines);
+        }
+    }
+    players[0].state.receive_garbage(sent[1]);
+    players[1].state.receive_garbage(sent[0]);
 
-    state.last_keyboard = keyboard;
+    let winner = match (players[0].state.over, players[1].state.over) {
+        (false, false) => {
+            for (i, player) in players.iter().enumerate() {
+                render(&player.state, &Layout::versus(i), now, frame);
+            }
+            return Screen::Playing;
+        },
+        (true, false) => Some(1),
+        (false, true) => Some(0),
+        (true, true) => None
+    };
+    Screen::VersusOver { winner }
 }
 
-// shorthand for a translation that is used a lot
-fn translated_list(state: &GameState, x_trans: isize, 
-    y_trans: isize) -> Option<BlockList> {
+// the other player is on another machine, the garbage and
+// the boards are passed back and forth over the serial port
+fn run_link(player: &mut Player, link: &mut Link, now: Instant,
+    frame: &mut TTYFrame) -> Screen {
 
-    // prevent underflow of unsigned type
-    if (state.block_x as isize) + x_trans < 0 || 
-        (state.block_y as isize) + y_trans < 0 {
+    player.update(now);
+    let state = &mut player.state;
+    let sent = state.take_sent_garbage();
+    if sent > 0 {
+        debug!("sent {} lines of garbage", sent);
+    }
+    link.send_garbage(sent);
+
+    while let Some(event) = link.poll(now) {
+        match event {
+            LinkEvent::Garbage(lines) => {
+                debug!("received {} lines of garbage", lines);
+                state.receive_garbage(lines);
+            },
+            LinkEvent::KnockedOut => {
+                info!("the other machine topped out");
+                return Screen::LinkOver { result: LinkResult::Won };
+            },
+            LinkEvent::TimedOut => {
+                warn!("lost the link, nothing heard for too long");
+                return Screen::LinkOver { result: LinkResult::Disconnected };
+            },
+            LinkEvent::Start(_) => {}
+        }
+    }
 
-        return None;
+    if state.over {
+        link.send_knocked_out();
+        return Screen::LinkOver { result: LinkResult::Lost };
     }
 
-    let x = ((state.block_x as isize) + x_trans) as usize;
-    let y = ((state.block_y as isize) + y_trans) as usize;
-    block_list::list(&state.board, x, y, state.block_type, state.block_rotation)
+    link.send_snapshot(&state.board, state.incoming_garbage, now);
+    render(state, &Layout::link(), now, frame);
+    frame.render_opponent(&link.opponent);
+    Screen::Playing
 }
 
-fn handle_keyboard(state: &mut GameState, keyboard: &KeyboardState, time: u64) {
-    // move down on hold, even if pressed before
-    if keyboard.is_key_down(Key::DownArrow) &&
-        state.last_keyboard.is_key_down(Key::DownArrow) {
+fn render(state: &GameState, layout: &Layout, now: Instant, frame: &mut TTYFrame) {
+    frame.set_write_bounds(layout.hud);
 
-        if let Some(_) = translated_list(state, 0, 1) {
-            state.block_y += 1;
-        }
+    frame.print_score(&state.score, state.level, state.lines_cleared);
+    // there is no room for it beside the boards in versus
+    if state.mode.players() == 1 {
+        frame.print_mode_status(state.mode, state.elapsed(now.as_millis()),
+            state.lines_cleared, state.board.marked_rows());
     }
-
-    // handle all the other ones, only if an actual event occured
-    if state.last_keyboard.input_version() != keyboard.input_version() { 
-        if keyboard.is_key_down(Key::Space) {
-            while let Some(_) = translated_list(state, 0, 1) {
-                state.block_y += 1;
-            }
-            solidify_piece(state, &translated_list(state, 0, 0).unwrap(), time);
-        }
-        if keyboard.is_key_down(Key::LeftArrow) {
-            if let Some(_) = translated_list(state, -1, 0) {
-                state.block_x -= 1;
-            }
+    if let Some((clear, time)) = state.callout {
+        if now.duration_since(Instant::from_millis(time)) < CALLOUT_TIME {
+            frame.print_callout(clear);
         }
-        if keyboard.is_key_down(Key::RightArrow) { 
-            if let Some(_) = translated_list(state, 1, 0) {
-                state.block_x += 1;
-            }
-        }
-        if keyboard.is_key_down(Key::UpArrow) {
-            let rotation = state.block_rotation.right_rotate();
-            if let Some(_) = block_list::list(&state.board, state.block_x,
-                state.block_y, state.block_type, rotation) {
-                state.block_rotation = rotation;
+    }
+    if let Some((block_type, list)) = state.piece() {
+        if state.settings.ghost {
+            if let Some(ghost) = state.ghost() {
+                frame.render_ghost(layout, display::color_of(block_type), &ghost);
             }
         }
-        
+        frame.render_shape(layout, display::color_of(block_type), &list);
     }
-    
+    frame.render_stale(layout, &state.board);
+    frame.render_outline(layout);
+    frame.render_garbage_meter(layout, state.incoming_garbage);
+    frame.render_queue(layout, state.previews());
+    frame.render_hold(layout, state.held, state.hold_used);
 }
 
-fn solidify_piece(state: &mut GameState, list: &BlockList, time: u64) {
-    for (x, y) in list {
-        state.board[*x][*y] = BlockState::Block(state.block_type.color());
-    }
-
-    state.block_y = 1; // to avoid overflow
-    state.block_x = 5; 
-    state.block_type = BlockType::random(time);
-    state.block_rotation = BlockRotation::Zero;
-    
-    // check if lines need to be cleared
-    let mut line_clear_amt = 0;
-    let mut line_clear_end = 0;
-    for j in 0..BOARD_HEIGHT {
-        let mut all_covered = true;
-        for i in 0..BOARD_WIDTH {
-            if let BlockState::Empty = state.board[i][j] {
-                all_covered = false;
+// gives back the screen to show after the key
+fn handle_key_press(screen: Screen, players: &mut [Player; MAX_PLAYERS],
+    settings: &mut Settings, bindings: &mut Bindings, link: &mut Link, key: KeyCode,
+    now: Instant) -> Screen {
+
+    let mode = players[0].state.mode;
+    let active = &mut players[..mode.players()];
+    match screen {
+        Screen::Title(mut menu) => match menu.handle_key(key) {
+            // the modes come first in the menu, then the settings and the controls
+            Some(i) if Mode::ALL.get(i) == Some(&Mode::Link) => {
+                players[0].state.mode = Mode::Link;
+                link.connect(entropy::seed(), now);
+                Screen::Connecting
+            },
+            Some(i) if i < Mode::ALL.len() => {
+                restart(players, settings, bindings, Mode::ALL[i], entropy::seed(), now);
+                Screen::Playing
+            },
+            Some(i) if i == Mode::ALL.len() => Screen::Settings(SettingsMenu::new()),
+            Some(_) => Screen::Controls { menu: ControlsMenu::new(), from_pause: false },
+            None => Screen::Title(menu)
+        },
+        Screen::Playing => {
+            // the other machine keeps going, so instead of pausing it gives up
+            if mode == Mode::Link && is_pause_key(active, key) {
+                link.send_knocked_out();
+                return Screen::LinkOver { result: LinkResult::Lost };
             }
-        }
 
-        if all_covered {
-            line_clear_amt += 1;
-            line_clear_end = j;
-        }
-    }
+            // escape always pauses, so the menus can not be locked out
+            if is_pause_key(active, key) {
+                debug!("paused");
+                for player in active.iter_mut() {
+                    player.state.pause(now.as_millis());
+                }
+                return Screen::paused();
+            }
 
-    // clear lines if necessary
-    for j in (0..=line_clear_end).rev() {
-        for i in 0..BOARD_WIDTH {
-            if j < line_clear_amt {
-                state.board[i][j] = BlockState::Empty;
+            // the players never share keys, so at most one of them acts
+            for player in active.iter_mut() {
+                if let Some(action) = player.bindings.action_for(key) {
+                    player.input.press(action);
+                }
+            }
+            Screen::Playing
+        },
+        Screen::Paused(mut menu) => {
+            if is_pause_key(active, key) {
+                resume(active, now);
+                return Screen::Playing;
+            }
+
+            match menu.handle_key(key) {
+                Some(0) => {
+                    resume(active, now);
+                    Screen::Playing
+                },
+                Some(1) => {
+                    restart(players, settings, bindings, mode, entropy::seed(), now);
+                    Screen::Playing
+                },
+                Some(2) => Screen::Controls { menu: ControlsMenu::new(), from_pause: true },
+                Some(_) => Screen::title(),
+                None => Screen::Paused(menu)
+            }
+        },
+        Screen::Settings(mut menu) => match menu.handle_key(key, settings) {
+            true => Screen::Settings(menu),
+            false => Screen::title()
+        },
+        Screen::Controls { mut menu, from_pause } => {
+            if menu.handle_key(key, bindings) {
+                Screen::Controls { menu, from_pause }
+            }
+            else if from_pause {
+                // versus always uses its own keys
+                if mode.players() == 1 {
+                    players[0].bindings = *bindings;
+                }
+                Screen::paused()
             }
             else {
-                state.board[i][j] = state.board[i][j - line_clear_amt];
+                Screen::title()
             }
+        },
+        Screen::GameOver | Screen::Finished { .. } | Screen::VersusOver { .. } => match key {
+            KeyCode::Enter => {
+                restart(players, settings, bindings, mode, entropy::seed(), now);
+                Screen::Playing
+            },
+            KeyCode::Escape => Screen::title(),
+            _ => screen
+        },
+        Screen::Connecting => match key {
+            KeyCode::Escape => {
+                link.disconnect();
+                Screen::title()
+            },
+            _ => screen
+        },
+        Screen::LinkOver { .. } => match key {
+            KeyCode::Enter => {
+                link.connect(entropy::seed(), now);
+                Screen::Connecting
+            },
+            KeyCode::Escape => Screen::title(),
+            _ => screen
         }
     }
+}
 
-    state.lines_cleared += line_clear_amt as u64;
-
+fn is_pause_key(players: &[Player], key: KeyCode) -> bool {
+    key == KeyCode::Escape || players.iter()
+        .any(|player| player.bindings.action_for(key) == Some(Action::Pause))
 }
 
+fn resume(players: &mut [Player], now: Instant) {
+    debug!("resumed");
+    for player in players.iter_mut() {
+        player.state.resume(now.as_millis());
+    }
+}
 
+// throws away the old games and starts new ones. every player
+// gets the same seed, so they are dealt the same pieces
+fn restart(players: &mut [Player; MAX_PLAYERS], settings: &Settings,
+    bindings: &Bindings, mode: Mode, seed: u64, now: Instant) {
+
+    info!("starting {} with seed {:#x}", mode.name(), seed);
+    for (i, player) in players.iter_mut().take(mode.players()).enumerate() {
+        let bindings = match mode.players() {
+            1 => *bindings,
+            _ => Bindings::versus(i)
+        };
+        *player = Player::new(GameState::new(*settings, mode, seed, now.as_millis()), bindings);
+    }
+}
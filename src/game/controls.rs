@@ -0,0 +1,179 @@
+use crate::kernel::cmos;
+use crate::kernel::keyboard::{ self, KeyCode };
+use tetris_rules::input::{ Action, ACTION_COUNT };
+
+// each action can be on two keys at once
+pub const SLOT_COUNT: usize = 2;
+
+// which keys trigger each action, in the same order as Action::ALL
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Bindings {
+    keys: [[Option<KeyCode>; SLOT_COUNT]; ACTION_COUNT]
+}
+
+impl Default for Bindings {
+    fn default() -> Bindings {
+        use KeyCode::*;
+        Bindings {
+            keys: [
+                [Some(LeftArrow), None],
+                [Some(RightArrow), None],
+                [Some(DownArrow), None],
+                [Some(Space), None],
+                [Some(UpArrow), Some(X)],
+                [Some(Z), Some(LeftControl)],
+                [Some(A), None],
+                [Some(C), Some(LeftShift)],
+                [Some(Escape), Some(P)]
+            ]
+        }
+    }
+}
+
+impl Bindings {
+    // the fixed keys for each side of a versus game, player 1 is on the
+    // left of the keyboard and player 2 is on the arrows. escape pauses
+    pub fn versus(player: usize) -> Bindings {
+        use KeyCode::*;
+        let keys = match player {
+            0 => [
+                [Some(A), None], [Some(D), None], [Some(S), None], [Some(W), None],
+                [Some(E), None], [Some(Q), None], [Some(R), None], [Some(LeftShift), None],
+                [None, None]
+            ],
+            _ => [
+                [Some(LeftArrow), None], [Some(RightArrow), None],
+                [Some(DownArrow), None], [Some(UpArrow), None],
+                [Some(Period), None], [Some(Comma), None], [Some(Slash), None],
+                [Some(RightShift), None], [None, None]
+            ]
+        };
+        Bindings { keys }
+    }
+}
+
+// the layout at the start of the nvram is a magic number, then one key id
+// per slot with 0 meaning unbound, then a checksum of the key ids
+const MAGIC: [u8; 2] = [0x7e, 0x75];
+pub const SAVED_SIZE: usize = MAGIC.len() + ACTION_COUNT * SLOT_COUNT + 1;
+
+impl Bindings {
+    pub fn action_for(&self, key: KeyCode) -> Option<Action> {
+        let index = self.keys.iter().position(|slots| slots.contains(&Some(key)))?;
+        Some(Action::ALL[index])
+    }
+
+    pub fn key(&self, action: Action, slot: usize) -> Option<KeyCode> {
+        self.keys[action as usize][slot]
+    }
+
+    // a key can only do one thing, so it is taken off any other action first
+    pub fn bind(&mut self, action: Action, slot: usize, key: Option<KeyCode>) {
+        if key.is_some() {
+            for slots in self.keys.iter_mut() {
+                for bound in slots.iter_mut() {
+                    if *bound == key {
+                        *bound = None;
+                    }
+                }
+            }
+        }
+
+        self.keys[action as usize][slot] = key;
+    }
+
+    pub fn is_down(&self, action: Action) -> bool {
+        self.keys[action as usize].iter()
+            .any(|key| key.is_some_and(keyboard::is_key_down))
+    }
+
+    // the bindings saved in the cmos, if there are any that are valid
+    pub fn load() -> Option<Bindings> {
+        let mut saved = [0; SAVED_SIZE];
+        cmos::read_nvram(0, &mut saved);
+        if saved[..MAGIC.len()] != MAGIC {
+            return None;
+        }
+
+        let ids = &saved[MAGIC.len()..SAVED_SIZE - 1];
+        if checksum(ids) != saved[SAVED_SIZE - 1] {
+            return None;
+        }
+
+        let mut bindings = Bindings { keys: [[None; SLOT_COUNT]; ACTION_COUNT] };
+        for (i, id) in ids.iter().enumerate() {
+            if *id != 0 {
+                bindings.keys[i / SLOT_COUNT][i % SLOT_COUNT] = Some(KeyCode::from_id(*id)?);
+            }
+        }
+        Some(bindings)
+    }
+
+    pub fn save(&self) {
+        let mut saved = [0; SAVED_SIZE];
+        saved[..MAGIC.len()].copy_from_slice(&MAGIC);
+        for (i, slots) in self.keys.iter().enumerate() {
+            for (j, key) in slots.iter().enumerate() {
+                saved[MAGIC.len() + i * SLOT_COUNT + j] = key.map_or(0, KeyCode::id);
+            }
+        }
+        saved[SAVED_SIZE - 1] = checksum(&saved[MAGIC.len()..SAVED_SIZE - 1]);
+        cmos::write_nvram(0, &saved);
+    }
+}
+
+pub fn checksum(bytes: &[u8]) -> u8 {
+    bytes.iter().fold(0u8, |sum, byte| sum.rotate_left(1) ^ byte)
+}
+
+// the screen for changing the bindings, the arrows pick an action and
+// which of its keys, enter waits for the new key, and delete unbinds it
+#[derive(Copy, Clone, Debug)]
+pub struct ControlsMenu {
+    pub selected: usize,
+    pub slot: usize,
+    pub waiting: bool
+}
+
+impl ControlsMenu {
+    pub fn new() -> ControlsMenu {
+        ControlsMenu {
+            selected: 0,
+            slot: 0,
+            waiting: false
+        }
+    }
+
+    // gives back false once the menu is closed
+    pub fn handle_key(&mut self, key: KeyCode, bindings: &mut Bindings) -> bool {
+        if self.waiting {
+            // escape cancels, so it can only be bound as the default
+            if key != KeyCode::Escape {
+                bindings.bind(Action::ALL[self.selected], self.slot, Some(key));
+            }
+            self.waiting = false;
+            return true;
+        }
+
+        match key {
+            KeyCode::UpArrow => {
+                self.selected = (self.selected + ACTION_COUNT - 1) % ACTION_COUNT;
+            },
+            KeyCode::DownArrow => self.selected = (self.selected + 1) % ACTION_COUNT,
+            KeyCode::LeftArrow | KeyCode::RightArrow => {
+                self.slot = (self.slot + 1) % SLOT_COUNT;
+            },
+            KeyCode::Enter => self.waiting = true,
+            KeyCode::Delete | KeyCode::Backspace => {
+                bindings.bind(Action::ALL[self.selected], self.slot, None);
+            },
+            KeyCode::Escape => {
+                bindings.save();
+                return false;
+            },
+            _ => {}
+        }
+
+        true
+    }
+}
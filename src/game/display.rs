@@ -1,63 +1,484 @@
-use crate::kernel::graphics::{ TTYFrame, Color };
+use crate::kernel::graphics::{ self, TTYFrame, TTYBounds, Color };
 use core::fmt::Write;
-use crate::kernel::interrupts::{ Key, KeyboardState };
-use super::{ BlockList, BlockState, BOARD_HEIGHT, BOARD_WIDTH };
+use crate::kernel::keyboard::{ self, KeyCode };
+use tetris_rules::{ BlockList, BlockState, BlockType, BlockRotation, BOARD_HEIGHT, BOARD_WIDTH,
+    MAX_PREVIEW };
+use tetris_rules::block_list;
+use tetris_rules::board::Board;
+use tetris_rules::input::Action;
+use tetris_rules::scoring::{ ClearType, Score };
+use tetris_rules::modes::{ BestTimes, Mode };
+use super::controls::{ Bindings, ControlsMenu, SLOT_COUNT };
+use super::menu::{ Menu, TITLE_ITEMS, PAUSE_ITEMS };
+use super::settings::{ Setting, SettingsMenu };
+use tetris_rules::settings::Settings;
+use super::link::{ LinkResult, Snapshot };
+
+const HOLD_WIDTH: usize = 10;
+// the board with its outline, each block is 2 characters wide
+const BOARD_SCREEN_WIDTH: usize = BOARD_WIDTH * 2 + 4;
+
+const QUEUE_Y: usize = 1;
+// each preview is 2 rows tall with a gap of one row under it
+const QUEUE_SPACING: usize = 3;
+
+// the small view of the other machine's board, on the right edge of the screen
+const OPPONENT_X: usize = graphics::WIDTH - BOARD_WIDTH - 2;
+
+const GARBAGE_COLOR: Color = Color::DarkGray;
+
+// cp437 light shade, used to draw the ghost piece
+const GHOST_CHAR: u8 = 0xb0;
+
+// where each part of one player's game goes on screen
+#[derive(Copy, Clone, Debug)]
+pub struct Layout {
+    board_x: usize,
+    hold_x: usize,
+    hold_y: usize,
+    queue_x: usize,
+    // how many previews fit under the queue
+    pub max_previews: usize,
+    // where the score and other text goes
+    pub hud: TTYBounds
+}
+
+impl Layout {
+    // the hold box takes the left of the screen, and the board is moved over for it.
+    // the queue is to the right of the board, and the text to the right of that
+    pub fn single() -> Layout {
+        let board_x = HOLD_WIDTH + 2;
+        let queue_x = board_x + BOARD_SCREEN_WIDTH + 1;
+        Layout {
+            board_x,
+            hold_x: 0,
+            hold_y: 0,
+            queue_x,
+            max_previews: MAX_PREVIEW,
+            hud: TTYBounds {
+                x: queue_x + 11, y: 1,
+                end_x: graphics::WIDTH - 1, end_y: graphics::HEIGHT - 1
+            }
+        }
+    }
+
+    // the same as one player, but the text makes room for the other board
+    pub fn link() -> Layout {
+        let mut layout = Layout::single();
+        layout.hud.end_x = OPPONENT_X - 2;
+        layout
+    }
+
+    // each player gets half of the screen, so the hold box
+    // and the text go under a shorter queue
+    pub fn versus(player: usize) -> Layout {
+        let board_x = player * graphics::WIDTH / 2;
+        let queue_x = board_x + BOARD_SCREEN_WIDTH + 1;
+        Layout {
+            board_x,
+            hold_x: queue_x,
+            hold_y: 11,
+            queue_x,
+            max_previews: 3,
+            hud: TTYBounds {
+                x: queue_x, y: 17,
+                end_x: queue_x + 13, end_y: graphics::HEIGHT - 1
+            }
+        }
+    }
+}
+
+// how a game that reached its goal went, for the finish screen
+pub struct FinishStats {
+    // milliseconds from the start to the goal
+    pub time: u64,
+    pub points: u64,
+    pub lines: u64,
+    pub pieces: u64
+}
 
 impl TTYFrame {
     
-    pub fn render_outline(&mut self) {
-        self.draw_line_verticale(0, 0, BOARD_HEIGHT, Color::DarkGray);
-        self.draw_line_verticale(1, 0, BOARD_HEIGHT, Color::DarkGray);
+    pub fn render_outline(&mut self, layout: &Layout) {
+        let board_x = layout.board_x;
+        self.draw_line_verticale(board_x, 0, BOARD_HEIGHT, Color::DarkGray);
+        self.draw_line_verticale(board_x + 1, 0, BOARD_HEIGHT, Color::DarkGray);
 
-        self.draw_line_verticale(22, 0, BOARD_HEIGHT, Color::DarkGray);
-        self.draw_line_verticale(23, 0, BOARD_HEIGHT, Color::DarkGray);
+        self.draw_line_verticale(board_x + 22, 0, BOARD_HEIGHT, Color::DarkGray);
+        self.draw_line_verticale(board_x + 23, 0, BOARD_HEIGHT, Color::DarkGray);
+
+        self.draw_line_horizontal(0, board_x, board_x + 21, Color::DarkGray);
+        self.draw_line_horizontal(BOARD_HEIGHT + 1, board_x, board_x + 23, Color::DarkGray);
+    }
+
+    // the garbage waiting to come up is drawn over the left of the outline
+    pub fn render_garbage_meter(&mut self, layout: &Layout, incoming: u64) {
+        let rows = (incoming as usize).min(BOARD_HEIGHT);
+        if rows == 0 {
+            return;
+        }
 
-        self.draw_line_horizontal(0, 0, 21, Color::DarkGray);
-        self.draw_line_horizontal(BOARD_HEIGHT + 1, 0, 23, Color::DarkGray);
+        let top = BOARD_HEIGHT + 1 - rows;
+        self.draw_line_verticale(layout.board_x, top, BOARD_HEIGHT, Color::Red);
+        self.draw_line_verticale(layout.board_x + 1, top, BOARD_HEIGHT, Color::Red);
     }
 
-    pub fn render_stale(&mut self, board: &[[BlockState; BOARD_HEIGHT]; BOARD_WIDTH]) {
+    pub fn render_stale(&mut self, layout: &Layout, board: &Board) {
         for i in 0..BOARD_WIDTH {
             for j in 0..BOARD_HEIGHT {
-                if let BlockState::Block(color) = board[i][j] {
-                    self.render_block(i, j, color);
+                match board.get(i, j) {
+                    BlockState::Block(block_type) => {
+                        self.render_block(layout, i, j, color_of(block_type));
+                    },
+                    BlockState::Garbage => self.render_block(layout, i, j, GARBAGE_COLOR),
+                    BlockState::Empty => {}
                 }
             }
         }
     }
 
-    pub fn print_end_screen(&mut self, lines: u64) {
-        core::write!(self, "game over, {} lines cleared", lines).unwrap();
+    pub fn print_end_screen(&mut self, score: u64, lines: u64) {
+        core::write!(self, "game over, {} points, {} lines cleared\n\n", score, lines).unwrap();
+        core::write!(self, "press enter to restart, or escape for the title").unwrap();
+    }
+
+    pub fn render_title(&mut self, menu: &Menu) {
+        core::write!(self, "tetris\n\n").unwrap();
+        let mut items = [""; Mode::ALL.len() + TITLE_ITEMS.len()];
+        for (i, mode) in Mode::ALL.iter().enumerate() {
+            items[i] = mode.name();
+        }
+        items[Mode::ALL.len()..].copy_from_slice(&TITLE_ITEMS);
+        self.render_menu(&items, menu);
+    }
+
+    pub fn render_finish(&mut self, mode: Mode, stats: &FinishStats,
+        best_times: &BestTimes, place: Option<usize>) {
+
+        // pieces per second, to 2 decimal places
+        let pps = stats.pieces * 100_000 / stats.time.max(1);
+        core::write!(self, "{} complete\n\n", mode.name()).unwrap();
+        core::writeln!(self, "time: {}", Clock(stats.time)).unwrap();
+        core::writeln!(self, "score: {}", stats.points).unwrap();
+        core::writeln!(self, "lines: {}", stats.lines).unwrap();
+        core::writeln!(self, "pieces: {}", stats.pieces).unwrap();
+        core::writeln!(self, "pieces per second: {}.{:02}", pps / 100, pps % 100).unwrap();
+
+        if mode.ranked_by_time() {
+            core::writeln!(self, "\nbest times").unwrap();
+            for (i, best) in best_times.times.iter().enumerate() {
+                if let Some(best) = best {
+                    let marker = if place == Some(i) { " <" } else { "" };
+                    core::writeln!(self, "{}. {}{}", i + 1, Clock(*best), marker).unwrap();
+                }
+            }
+        }
+
+        core::write!(self, "\npress enter to play again, or escape for the title").unwrap();
+    }
+
+    pub fn render_versus_over(&mut self, winner: Option<usize>, scores: [u64; 2]) {
+        match winner {
+            Some(winner) => core::write!(self, "player {} wins\n\n", winner + 1).unwrap(),
+            None => core::write!(self, "draw\n\n").unwrap()
+        }
+        for (i, score) in scores.iter().enumerate() {
+            core::writeln!(self, "player {}: {} points", i + 1, score).unwrap();
+        }
+        core::write!(self, "\npress enter for a rematch, or escape for the title").unwrap();
+    }
+
+    pub fn render_connecting(&mut self, present: bool) {
+        if present {
+            core::write!(self, "waiting for the other machine on com2\n\n").unwrap();
+        }
+        else {
+            core::write!(self, "there is no serial port on com2\n\n").unwrap();
+        }
+        core::write!(self, "press escape to go back").unwrap();
+    }
+
+    pub fn render_link_over(&mut self, result: LinkResult, score: u64) {
+        match result {
+            LinkResult::Won => core::write!(self, "you win\n\n").unwrap(),
+            LinkResult::Lost => core::write!(self, "you lose\n\n").unwrap(),
+            LinkResult::Disconnected => {
+                core::write!(self, "the other machine stopped answering\n\n").unwrap();
+            }
+        }
+        core::writeln!(self, "{} points", score).unwrap();
+        core::write!(self, "\npress enter for a rematch, or escape for the title").unwrap();
+    }
+
+    // one character for each block, in a single color
+    pub fn render_opponent(&mut self, snapshot: &Snapshot) {
+        let right = OPPONENT_X + BOARD_WIDTH + 1;
+        self.draw_line_verticale(OPPONENT_X, 0, BOARD_HEIGHT, Color::DarkGray);
+        self.draw_line_verticale(right, 0, BOARD_HEIGHT, Color::DarkGray);
+        self.draw_line_horizontal(BOARD_HEIGHT + 1, OPPONENT_X, right, Color::DarkGray);
+
+        for y in 0..BOARD_HEIGHT {
+            for x in 0..BOARD_WIDTH {
+                if snapshot.is_filled(x, y) {
+                    self.draw_square(OPPONENT_X + 1 + x, y + 1, Color::LightGray);
+                }
+            }
+        }
+
+        let rows = (snapshot.incoming as usize).min(BOARD_HEIGHT);
+        if rows > 0 {
+            self.draw_line_verticale(OPPONENT_X, BOARD_HEIGHT + 1 - rows,
+                BOARD_HEIGHT, Color::Red);
+        }
+    }
+
+    pub fn render_pause(&mut self, menu: &Menu) {
+        core::write!(self, "paused\n\n").unwrap();
+        self.render_menu(&PAUSE_ITEMS, menu);
+    }
+
+    fn render_menu(&mut self, items: &[&str], menu: &Menu) {
+        for (i, item) in items.iter().enumerate() {
+            let marker = if i == menu.selected { '>' } else { ' ' };
+            core::writeln!(self, "{} {}", marker, item).unwrap();
+        }
+    }
+
+    pub fn print_score(&mut self, score: &Score, level: u64, lines: u64) {
+        core::writeln!(self, "score: {}", score.points).unwrap();
+        core::writeln!(self, "level: {}", level).unwrap();
+        core::writeln!(self, "lines: {}", lines).unwrap();
+        if let Some(combo) = score.combo {
+            if combo > 0 {
+                core::write!(self, "combo: {}", combo).unwrap();
+            }
+        }
+    }
+
+    // the timer and goal of the mode, if it has them
+    pub fn print_mode_status(&mut self, mode: Mode, elapsed: u64, lines: u64,
+        garbage_left: usize) {
+        match mode.time_limit() {
+            Some(limit) => {
+                let left = limit.saturating_sub(elapsed);
+                core::write!(self, "\ntime left: {}", Clock(left)).unwrap();
+            },
+            None if mode != Mode::Endless => {
+                core::write!(self, "\ntime: {}", Clock(elapsed)).unwrap();
+            },
+            None => {}
+        }
+
+        if let Some(goal) = mode.line_goal() {
+            core::write!(self, "\nlines left: {}", goal.saturating_sub(lines)).unwrap();
+        }
+        if mode.garbage_rows().is_some() {
+            core::write!(self, "\ngarbage left: {}", garbage_left).unwrap();
+        }
     }
 
-    pub fn print_score(&mut self, lines: u64) {
-        core::write!(self, "lines cleares: {}", lines).unwrap();
+    pub fn print_callout(&mut self, clear: ClearType) {
+        if let Some(text) = clear.callout() {
+            core::write!(self, "\n\n{}", text).unwrap();
+        }
     }
 
-    pub fn print_keyboard_state(&mut self, current_time: u64, last_time: u64, keyboard: &KeyboardState) {
-        core::write!(self, "time is: {}, last is: {}\n", current_time, last_time).unwrap();
-        core::write!(self, "up arrow is down: {}\n", keyboard.is_key_down(Key::UpArrow)).unwrap();    
-        core::write!(self, "down arrow is down: {}\n", keyboard.is_key_down(Key::DownArrow)).unwrap();    
-        core::write!(self, "left arrow is down: {}\n", keyboard.is_key_down(Key::LeftArrow)).unwrap();    
-        core::write!(self, "right arrow is down: {}\n", keyboard.is_key_down(Key::RightArrow)).unwrap();    
-        core::write!(self, "space is down: {}", keyboard.is_key_down(Key::Space)).unwrap();    
+    pub fn render_controls(&mut self, menu: &ControlsMenu, bindings: &Bindings) {
+        core::write!(self, "controls\n\n").unwrap();
+        for (i, action) in Action::ALL.iter().enumerate() {
+            let marker = if i == menu.selected { '>' } else { ' ' };
+            core::write!(self, "{} {:<14}", marker, action.name()).unwrap();
+
+            for slot in 0..SLOT_COUNT {
+                let selected = i == menu.selected && slot == menu.slot;
+                let (open, close) = if selected { ('[', ']') } else { (' ', ' ') };
+                if selected && menu.waiting {
+                    core::write!(self, "{}{:<14}{}", open, "press a key", close).unwrap();
+                }
+                else if let Some(key) = bindings.key(*action, slot) {
+                    let mut name = NameBuffer::new();
+                    core::write!(&mut name, "{:?}", key).unwrap();
+                    core::write!(self, "{}{:<14}{}", open, name.as_str(), close).unwrap();
+                }
+                else {
+                    core::write!(self, "{}{:<14}{}", open, "-", close).unwrap();
+                }
+            }
+            core::writeln!(self).unwrap();
+        }
+
+        core::write!(self, "\narrows to pick, enter to change, delete to clear, \
+            escape to go back").unwrap();
+    }
+
+    pub fn render_settings(&mut self, menu: &SettingsMenu, settings: &Settings) {
+        core::writeln!(self, "settings\n").unwrap();
+        for (i, setting) in Setting::ALL.iter().enumerate() {
+            let marker = if i == menu.selected { '>' } else { ' ' };
+            core::write!(self, "{} {:<18}", marker, setting.name()).unwrap();
+            match setting {
+                Setting::Ghost => {
+                    core::write!(self, "{}", if settings.ghost { "on" } else { "off" }).unwrap();
+                },
+                Setting::Randomizer => {
+                    core::write!(self, "{}", settings.randomizer.name()).unwrap();
+                },
+                Setting::Previews => {
+                    core::write!(self, "{}", settings.visible_previews()).unwrap();
+                },
+                Setting::LockDelay => {
+                    core::write!(self, "{}ms", settings.lock_delay).unwrap();
+                },
+                Setting::Das => core::write!(self, "{}ms", settings.das).unwrap(),
+                Setting::Arr => match settings.arr {
+                    0 => core::write!(self, "instant").unwrap(),
+                    arr => core::write!(self, "{}ms", arr).unwrap()
+                },
+                Setting::SoftDrop => match settings.soft_drop_rate {
+                    0 => core::write!(self, "instant").unwrap(),
+                    rate => core::write!(self, "{}ms", rate).unwrap()
+                }
+            }
+            core::writeln!(self).unwrap();
+        }
+
+        core::write!(self, "\nup and down to pick, left and right to change, \
+            escape to go back").unwrap();
     }
 
-    pub fn render_shape(&mut self, color: Color, 
+    pub fn print_keyboard_state(&mut self, current_time: u64, last_time: u64) {
+        core::writeln!(self, "time is: {}, last is: {}", current_time, last_time).unwrap();
+        core::writeln!(self, "up arrow is down: {}", keyboard::is_key_down(KeyCode::UpArrow)).unwrap();    
+        core::writeln!(self, "down arrow is down: {}", keyboard::is_key_down(KeyCode::DownArrow)).unwrap();    
+        core::writeln!(self, "left arrow is down: {}", keyboard::is_key_down(KeyCode::LeftArrow)).unwrap();    
+        core::writeln!(self, "right arrow is down: {}", keyboard::is_key_down(KeyCode::RightArrow)).unwrap();    
+        core::write!(self, "space is down: {}", keyboard::is_key_down(KeyCode::Space)).unwrap();    
+    }
+
+    pub fn render_shape(&mut self, layout: &Layout, color: Color, 
         list: &BlockList) {
 
         for (x, y) in list {
-            self.render_block(*x, *y, color);
+            self.render_block(layout, *x, *y, color);
+        }
+    }
+
+    pub fn render_queue(&mut self, layout: &Layout, queue: &[BlockType]) {
+        let queue_x = layout.queue_x;
+        self.set_write_bounds(TTYBounds {
+            x: queue_x, y: QUEUE_Y - 1,
+            end_x: queue_x + 9, end_y: QUEUE_Y - 1
+        });
+        core::write!(self, "next").unwrap();
+
+        for (i, block_type) in queue.iter().take(layout.max_previews).enumerate() {
+            let y = QUEUE_Y + 1 + i * QUEUE_SPACING;
+            self.render_piece(queue_x + 2, y, *block_type, color_of(*block_type));
+        }
+    }
+
+    // the held piece is grayed out while it can not be swapped
+    pub fn render_hold(&mut self, layout: &Layout, held: Option<BlockType>, used: bool) {
+        let (x, y) = (layout.hold_x, layout.hold_y);
+        self.draw_line_horizontal(y, x, x + HOLD_WIDTH - 1, Color::DarkGray);
+        self.draw_line_horizontal(y + 5, x, x + HOLD_WIDTH - 1, Color::DarkGray);
+        self.draw_line_verticale(x, y, y + 5, Color::DarkGray);
+        self.draw_line_verticale(x + HOLD_WIDTH - 1, y, y + 5, Color::DarkGray);
+
+        self.set_write_bounds(TTYBounds {
+            x: x + 3, y,
+            end_x: x + HOLD_WIDTH - 2, end_y: y
+        });
+        core::write!(self, "hold").unwrap();
+
+        if let Some(block_type) = held {
+            let color = if used { Color::DarkGray } else { color_of(block_type) };
+            self.render_piece(x + 3, y + 3, block_type, color);
+        }
+    }
+
+    // draws a piece in its spawn rotation at a screen position, rather than at
+    // a position on the board. x and y are where the pivot block goes
+    pub fn render_piece(&mut self, x: usize, y: usize,
+        block_type: BlockType, color: Color) {
+
+        let offsets = block_list::offsets(block_type, BlockRotation::Zero);
+        for (dx, dy) in offsets.iter() {
+            let block_x = (x as isize + dx * 2) as usize;
+            let block_y = (y as isize + dy) as usize;
+            self.draw_line_horizontal(block_y, block_x, block_x + 1, color);
+        }
+    }
+
+    pub fn render_ghost(&mut self, layout: &Layout, color: Color, list: &BlockList) {
+        for (x, y) in list {
+            let screen_x = layout.board_x + x * 2 + 2;
+            self.draw_colored_char(screen_x, y + 1, GHOST_CHAR, color, Color::Black);
+            self.draw_colored_char(screen_x + 1, y + 1, GHOST_CHAR, color, Color::Black);
         }
     }
 
     // render block with their logical game position
-    fn render_block(&mut self, x: usize, y: usize, color: Color) {
-        self.draw_line_horizontal(y + 1, x * 2 + 2, x * 2 + 3, color);
+    fn render_block(&mut self, layout: &Layout, x: usize, y: usize, color: Color) {
+        let screen_x = layout.board_x + x * 2 + 2;
+        self.draw_line_horizontal(y + 1, screen_x, screen_x + 1, color);
+    }
+
+}
+
+
+
+
+
+pub fn color_of(block_type: BlockType) -> Color {
+    use BlockType::*;
+    match block_type {
+        I => Color::Cyan,
+        Z => Color::Red,
+        S => Color::Blue,
+        T => Color::Magenta,
+        Square => Color::Yellow,
+        J => Color::Green,
+        L => Color::Pink
     }
+}
+
+// milliseconds written out as minutes, seconds and milliseconds
+struct Clock(u64);
 
+impl core::fmt::Display for Clock {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let millis = self.0 % 1000;
+        let seconds = self.0 / 1000 % 60;
+        let minutes = self.0 / 60_000;
+        core::write!(f, "{}:{:02}.{:03}", minutes, seconds, millis)
+    }
 }
 
+// the debug name of a key ignores padding, so it is written here first
+struct NameBuffer {
+    bytes: [u8; 16],
+    len: usize
+}
 
+impl NameBuffer {
+    fn new() -> NameBuffer {
+        NameBuffer { bytes: [0; 16], len: 0 }
+    }
 
+    fn as_str(&self) -> &str {
+        core::str::from_utf8(&self.bytes[..self.len]).unwrap_or("")
+    }
+}
 
+impl core::fmt::Write for NameBuffer {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for byte in s.bytes() {
+            if self.len < self.bytes.len() {
+                self.bytes[self.len] = byte;
+                self.len += 1;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,472 @@
+use core::convert::TryInto;
+use crate::kernel::{ entropy, serial };
+use crate::kernel::time::{ Duration, Instant };
+use tetris_rules::board::Board;
+use tetris_rules::BOARD_HEIGHT;
+
+// every frame starts with this, so the reader can find
+// the next frame again after bytes were lost
+const SYNC: u8 = 0xa5;
+// the biggest message is a board snapshot
+const MAX_PAYLOAD: usize = BOARD_HEIGHT * 2 + 1;
+// sync, kind, length, the payload, then the checksum
+const MAX_FRAME: usize = MAX_PAYLOAD + 5;
+
+const READY: u8 = 1;
+const START: u8 = 2;
+const GARBAGE: u8 = 3;
+const ACK: u8 = 4;
+const BOARD: u8 = 5;
+const KNOCKED_OUT: u8 = 6;
+
+const READY_INTERVAL: Duration = Duration::from_millis(250);
+const SNAPSHOT_INTERVAL: Duration = Duration::from_millis(100);
+const GARBAGE_RESEND: Duration = Duration::from_millis(200);
+// the other machine is given up on after this long without a frame
+const TIMEOUT: Duration = Duration::from_secs(3);
+// knocked out is not acknowledged, so it is sent a few times
+const KNOCKED_OUT_COPIES: usize = 3;
+
+// what the other player's board looks like, for the small view of it
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Snapshot {
+    // one bit for each filled block, from the left
+    pub rows: [u16; BOARD_HEIGHT],
+    pub incoming: u8
+}
+
+impl Snapshot {
+    pub fn new() -> Snapshot {
+        Snapshot {
+            rows: [0; BOARD_HEIGHT],
+            incoming: 0
+        }
+    }
+
+    pub fn of(board: &Board, incoming: u64) -> Snapshot {
+        let mut rows = [0; BOARD_HEIGHT];
+        for (y, row) in rows.iter_mut().enumerate() {
+            *row = board.row_mask(y);
+        }
+        Snapshot { rows, incoming: incoming.min(u8::MAX as u64) as u8 }
+    }
+
+    pub fn is_filled(&self, x: usize, y: usize) -> bool {
+        self.rows[y] & 1 << x != 0
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Message {
+    // sent over and over while waiting, the side with the bigger nonce leads
+    Ready { nonce: u32 },
+    // from the leader, both games use the seed so they get the same pieces
+    Start { seed: u64 },
+    // lines of garbage, resent until it is acknowledged
+    Garbage { seq: u8, lines: u8 },
+    Ack { seq: u8 },
+    Board(Snapshot),
+    KnockedOut
+}
+
+impl Message {
+    // writes the whole frame, and gives back how long it is
+    fn encode(&self, frame: &mut [u8; MAX_FRAME]) -> usize {
+        let payload = &mut frame[3..3 + MAX_PAYLOAD];
+        let (kind, len) = match *self {
+            Message::Ready { nonce } => {
+                payload[..4].copy_from_slice(&nonce.to_le_bytes());
+                (READY, 4)
+            },
+            Message::Start { seed } => {
+                payload[..8].copy_from_slice(&seed.to_le_bytes());
+                (START, 8)
+            },
+            Message::Garbage { seq, lines } => {
+                payload[0] = seq;
+                payload[1] = lines;
+                (GARBAGE, 2)
+            },
+            Message::Ack { seq } => {
+                payload[0] = seq;
+                (ACK, 1)
+            },
+            Message::Board(snapshot) => {
+                for (i, row) in snapshot.rows.iter().enumerate() {
+                    payload[i * 2..i * 2 + 2].copy_from_slice(&row.to_le_bytes());
+                }
+                payload[BOARD_HEIGHT * 2] = snapshot.incoming;
+                (BOARD, MAX_PAYLOAD)
+            },
+            Message::KnockedOut => (KNOCKED_OUT, 0)
+        };
+
+        frame[0] = SYNC;
+        frame[1] = kind;
+        frame[2] = len as u8;
+        let sum = checksum(&frame[1..3 + len]);
+        frame[3 + len..5 + len].copy_from_slice(&sum.to_le_bytes());
+        len + 5
+    }
+
+    fn decode(kind: u8, payload: &[u8]) -> Option<Message> {
+        let message = match (kind, payload.len()) {
+            (READY, 4) => Message::Ready {
+                nonce: u32::from_le_bytes(payload.try_into().ok()?)
+            },
+            (START, 8) => Message::Start {
+                seed: u64::from_le_bytes(payload.try_into().ok()?)
+            },
+            (GARBAGE, 2) => Message::Garbage { seq: payload[0], lines: payload[1] },
+            (ACK, 1) => Message::Ack { seq: payload[0] },
+            (BOARD, MAX_PAYLOAD) => {
+                let mut snapshot = Snapshot::new();
+                for (i, row) in snapshot.rows.iter_mut().enumerate() {
+                    *row = u16::from_le_bytes([payload[i * 2], payload[i * 2 + 1]]);
+                }
+                snapshot.incoming = payload[BOARD_HEIGHT * 2];
+                Message::Board(snapshot)
+            },
+            (KNOCKED_OUT, 0) => Message::KnockedOut,
+            _ => return None
+        };
+        Some(message)
+    }
+}
+
+// fletcher-16, unlike a plain sum it also catches bytes in the wrong order
+fn checksum(bytes: &[u8]) -> u16 {
+    let (mut low, mut high) = (0u16, 0u16);
+    for byte in bytes {
+        low = (low + *byte as u16) % 255;
+        high = (high + low) % 255;
+    }
+    high << 8 | low
+}
+
+// puts frames back together from the bytes as they come in
+struct Decoder {
+    frame: [u8; MAX_FRAME],
+    len: usize
+}
+
+impl Decoder {
+    fn new() -> Decoder {
+        Decoder {
+            frame: [0; MAX_FRAME],
+            len: 0
+        }
+    }
+
+    // gives back the message once the last byte of a good frame is pushed,
+    // a frame with a bad length or checksum is thrown away
+    fn push(&mut self, byte: u8) -> Option<Message> {
+        if self.len == 0 && byte != SYNC {
+            return None;
+        }
+
+        self.frame[self.len] = byte;
+        self.len += 1;
+        if self.len < 3 {
+            return None;
+        }
+
+        let payload_len = self.frame[2] as usize;
+        if payload_len > MAX_PAYLOAD {
+            self.len = 0;
+            return None;
+        }
+        if self.len < payload_len + 5 {
+            return None;
+        }
+
+        self.len = 0;
+        let end = payload_len + 3;
+        let sum = u16::from_le_bytes([self.frame[end], self.frame[end + 1]]);
+        if checksum(&self.frame[1..end]) != sum {
+            return None;
+        }
+        Message::decode(self.frame[1], &self.frame[3..end])
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum LinkResult {
+    Won, Lost, Disconnected
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum LinkState {
+    Idle, Waiting, Playing
+}
+
+#[derive(Copy, Clone, Debug)]
+pub enum LinkEvent {
+    // both sides are ready, the game starts with the seed
+    Start(u64),
+    Garbage(u64),
+    KnockedOut,
+    TimedOut
+}
+
+// a versus game with another machine over the serial port
+pub struct Link {
+    state: LinkState,
+    nonce: u32,
+    seed: u64,
+    leader: bool,
+    last_ready: Instant,
+    // when the last good frame came in
+    last_heard: Instant,
+    last_snapshot: Instant,
+    // garbage is sent one message at a time, the next one
+    // goes once the one in flight is acknowledged
+    unsent: u64,
+    next_seq: u8,
+    // the sequence number, lines, and when it was last sent
+    in_flight: Option<(u8, u8, Instant)>,
+    // garbage that is sent again is only counted once
+    last_seq_received: Option<u8>,
+    decoder: Decoder,
+    pub opponent: Snapshot
+}
+
+impl Link {
+    pub fn new() -> Link {
+        Link {
+            state: LinkState::Idle,
+            nonce: 0,
+            seed: 0,
+            leader: false,
+            last_ready: Instant::BOOT,
+            last_heard: Instant::BOOT,
+            last_snapshot: Instant::BOOT,
+            unsent: 0,
+            next_seq: 0,
+            in_flight: None,
+            last_seq_received: None,
+            decoder: Decoder::new(),
+            opponent: Snapshot::new()
+        }
+    }
+
+    // throws away the last game, and waits for the other machine to be ready
+    pub fn connect(&mut self, seed: u64, now: Instant) {
+        *self = Link::new();
+        self.state = LinkState::Waiting;
+        self.nonce = (seed >> 32) as u32;
+        self.seed = seed;
+        self.send(Message::Ready { nonce: self.nonce });
+        self.last_ready = now;
+    }
+
+    pub fn disconnect(&mut self) {
+        self.state = LinkState::Idle;
+    }
+
+    pub fn send_garbage(&mut self, lines: u64) {
+        self.unsent += lines;
+    }
+
+    // the board is sent every so often, it also keeps the link from timing out
+    pub fn send_snapshot(&mut self, board: &Board, incoming: u64, now: Instant) {
+        if now - self.last_snapshot >= SNAPSHOT_INTERVAL {
+            self.send(Message::Board(Snapshot::of(board, incoming)));
+            self.last_snapshot = now;
+        }
+    }
+
+    pub fn send_knocked_out(&mut self) {
+        for _ in 0..KNOCKED_OUT_COPIES {
+            self.send(Message::KnockedOut);
+        }
+        self.state = LinkState::Idle;
+    }
+
+    // handles what came in over the serial port, and sends again whatever
+    // needs it. gives back one event at a time, so it is called until none
+    pub fn poll(&mut self, now: Instant) -> Option<LinkEvent> {
+        match self.state {
+            LinkState::Idle => {
+                // nothing is listening, so old frames do not pile up
+                while serial::link_receive().is_some() {}
+                return None;
+            },
+            LinkState::Waiting => {
+                if now - self.last_ready >= READY_INTERVAL {
+                    self.send(Message::Ready { nonce: self.nonce });
+                    self.last_ready = now;
+                }
+            },
+            LinkState::Playing => {
+                if now - self.last_heard >= TIMEOUT {
+                    self.state = LinkState::Idle;
+                    return Some(LinkEvent::TimedOut);
+                }
+                self.send_pending_garbage(now);
+            }
+        }
+
+        while let Some(byte) = serial::link_receive() {
+            if let Some(message) = self.decoder.push(byte) {
+                if let Some(event) = self.handle(message, now) {
+                    return Some(event);
+                }
+            }
+        }
+        None
+    }
+
+    fn handle(&mut self, message: Message, now: Instant) -> Option<LinkEvent> {
+        self.last_heard = now;
+        match (self.state, message) {
+            (LinkState::Waiting, Message::Ready { nonce }) => {
+                if self.nonce > nonce {
+                    self.leader = true;
+                    self.send(Message::Start { seed: self.seed });
+                    self.start(now);
+                    return Some(LinkEvent::Start(self.seed));
+                }
+                // both picked the same one, so neither would lead
+                if self.nonce == nonce {
+                    self.nonce = entropy::seed() as u32;
+                }
+                None
+            },
+            (LinkState::Waiting, Message::Start { seed }) => {
+                self.start(now);
+                Some(LinkEvent::Start(seed))
+            },
+            // the other side did not get the start
+            (LinkState::Playing, Message::Ready { .. }) if self.leader => {
+                self.send(Message::Start { seed: self.seed });
+                None
+            },
+            (LinkState::Playing, Message::Garbage { seq, lines }) => {
+                self.send(Message::Ack { seq });
+                if self.last_seq_received == Some(seq) {
+                    return None;
+                }
+                self.last_seq_received = Some(seq);
+                Some(LinkEvent::Garbage(lines as u64))
+            },
+            (LinkState::Playing, Message::Ack { seq }) => {
+                if let Some((in_flight, ..)) = self.in_flight {
+                    if in_flight == seq {
+                        self.in_flight = None;
+                    }
+                }
+                None
+            },
+            (LinkState::Playing, Message::Board(snapshot)) => {
+                self.opponent = snapshot;
+                None
+            },
+            (LinkState::Playing, Message::KnockedOut) => {
+                self.state = LinkState::Idle;
+                Some(LinkEvent::KnockedOut)
+            },
+            _ => None
+        }
+    }
+
+    fn start(&mut self, now: Instant) {
+        self.state = LinkState::Playing;
+        self.last_heard = now;
+    }
+
+    fn send_pending_garbage(&mut self, now: Instant) {
+        match self.in_flight {
+            Some((seq, lines, sent_at)) => {
+                if now - sent_at >= GARBAGE_RESEND {
+                    self.send(Message::Garbage { seq, lines });
+                    self.in_flight = Some((seq, lines, now));
+                }
+            },
+            None if self.unsent > 0 => {
+                let lines = self.unsent.min(u8::MAX as u64) as u8;
+                let seq = self.next_seq;
+                self.unsent -= lines as u64;
+                self.next_seq = seq.wrapping_add(1);
+                self.send(Message::Garbage { seq, lines });
+                self.in_flight = Some((seq, lines, now));
+            },
+            None => {}
+        }
+    }
+
+    fn send(&self, message: Message) {
+        let mut frame = [0; MAX_FRAME];
+        let len = message.encode(&mut frame);
+        serial::link_send(&frame[..len]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame_of(message: Message) -> ([u8; MAX_FRAME], usize) {
+        let mut frame = [0; MAX_FRAME];
+        let len = message.encode(&mut frame);
+        (frame, len)
+    }
+
+    // gives back what the last byte decoded to, none of the others should give anything
+    fn decode(decoder: &mut Decoder, bytes: &[u8]) -> Option<Message> {
+        let (last, rest) = bytes.split_last()?;
+        for byte in rest {
+            assert_eq!(decoder.push(*byte), None);
+        }
+        decoder.push(*last)
+    }
+
+    #[test_case]
+    fn messages_come_back_the_same() {
+        let mut snapshot = Snapshot::new();
+        snapshot.rows[BOARD_HEIGHT - 1] = 0b1111011111;
+        snapshot.incoming = 4;
+
+        let messages = [
+            Message::Ready { nonce: 0xdead_beef },
+            Message::Start { seed: 0x0123_4567_89ab_cdef },
+            Message::Garbage { seq: 7, lines: 3 },
+            Message::Ack { seq: 7 },
+            Message::Board(snapshot),
+            Message::KnockedOut
+        ];
+        let mut decoder = Decoder::new();
+        for message in messages.iter() {
+            let (frame, len) = frame_of(*message);
+            assert_eq!(decode(&mut decoder, &frame[..len]), Some(*message));
+        }
+    }
+
+    #[test_case]
+    fn a_bad_checksum_is_thrown_away() {
+        let (mut frame, len) = frame_of(Message::Garbage { seq: 1, lines: 2 });
+        frame[4] ^= 0x10;
+
+        let mut decoder = Decoder::new();
+        assert_eq!(decode(&mut decoder, &frame[..len]), None);
+
+        // the next good frame still comes through
+        let (frame, len) = frame_of(Message::Ack { seq: 1 });
+        assert_eq!(decode(&mut decoder, &frame[..len]), Some(Message::Ack { seq: 1 }));
+    }
+
+    #[test_case]
+    fn a_truncated_frame_is_not_decoded() {
+        let (frame, len) = frame_of(Message::Start { seed: 99 });
+        let mut decoder = Decoder::new();
+        assert_eq!(decode(&mut decoder, &frame[..len - 1]), None);
+
+        // the frame after it is eaten finishing the cut off one, and
+        // fails its checksum, but the reader finds the sync after that
+        let (frame, len) = frame_of(Message::Ack { seq: 2 });
+        for byte in frame[..len].iter() {
+            assert_eq!(decoder.push(*byte), None);
+        }
+        assert_eq!(decode(&mut decoder, &frame[..len]), Some(Message::Ack { seq: 2 }));
+    }
+}
@@ -0,0 +1,78 @@
+use crate::kernel::keyboard::KeyCode;
+use super::controls::ControlsMenu;
+use super::settings::SettingsMenu;
+use tetris_rules::modes::Mode;
+use super::link::LinkResult;
+
+// every mode is listed before these on the title screen
+pub const TITLE_ITEMS: [&str; 2] = ["settings", "controls"];
+pub const PAUSE_ITEMS: [&str; 4] = ["resume", "restart", "controls", "quit to title"];
+
+// what is on screen, the game only runs while playing
+#[derive(Copy, Clone, Debug)]
+pub enum Screen {
+    Title(Menu),
+    Playing,
+    // the board is hidden while paused
+    Paused(Menu),
+    // only on the title screen, the settings are for the next game
+    Settings(SettingsMenu),
+    Controls {
+        menu: ControlsMenu,
+        // where to go back to once the controls are closed
+        from_pause: bool
+    },
+    GameOver,
+    // the goal of the mode was reached, with the place
+    // in the best times if it made it in
+    Finished {
+        place: Option<usize>
+    },
+    // someone topped out in versus, none if both did at once
+    VersusOver {
+        winner: Option<usize>
+    },
+    // waiting for the other machine to be ready
+    Connecting,
+    LinkOver {
+        result: LinkResult
+    }
+}
+
+impl Screen {
+    pub fn title() -> Screen {
+        Screen::Title(Menu::new(Mode::ALL.len() + TITLE_ITEMS.len()))
+    }
+
+    pub fn paused() -> Screen {
+        Screen::Paused(Menu::new(PAUSE_ITEMS.len()))
+    }
+}
+
+// a list of items picked with the up and down arrows and enter
+#[derive(Copy, Clone, Debug)]
+pub struct Menu {
+    pub selected: usize,
+    count: usize
+}
+
+impl Menu {
+    pub fn new(count: usize) -> Menu {
+        Menu {
+            selected: 0,
+            count
+        }
+    }
+
+    // gives back the item that was picked, if enter was pressed
+    pub fn handle_key(&mut self, key: KeyCode) -> Option<usize> {
+        match key {
+            KeyCode::UpArrow => self.selected = (self.selected + self.count - 1) % self.count,
+            KeyCode::DownArrow => self.selected = (self.selected + 1) % self.count,
+            KeyCode::Enter => return Some(self.selected),
+            _ => {}
+        }
+
+        None
+    }
+}
@@ -0,0 +1,209 @@
+use crate::kernel::cmos;
+use crate::kernel::keyboard::KeyCode;
+use tetris_rules::MAX_PREVIEW;
+use tetris_rules::randomizer::RandomizerKind;
+use tetris_rules::settings::Settings;
+use super::controls;
+
+// what the settings menu can change, in the order it lists them
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Setting {
+    Ghost,
+    Randomizer,
+    Previews,
+    LockDelay,
+    Das,
+    Arr,
+    SoftDrop
+}
+
+impl Setting {
+    pub const ALL: [Setting; 7] = [
+        Setting::Ghost, Setting::Randomizer, Setting::Previews, Setting::LockDelay,
+        Setting::Das, Setting::Arr, Setting::SoftDrop
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Setting::Ghost => "ghost piece",
+            Setting::Randomizer => "randomizer",
+            Setting::Previews => "next pieces",
+            Setting::LockDelay => "lock delay",
+            Setting::Das => "auto shift delay",
+            Setting::Arr => "auto repeat rate",
+            Setting::SoftDrop => "soft drop rate"
+        }
+    }
+
+    // forward is to the right. the choices wrap around,
+    // and the numbers stop at the ends of their range
+    fn change(self, settings: &mut Settings, forward: bool) {
+        match self {
+            Setting::Ghost => settings.ghost = !settings.ghost,
+            Setting::Randomizer => {
+                let count = RandomizerKind::ALL.len();
+                let index = settings.randomizer as usize;
+                let index = (if forward { index + 1 } else { index + count - 1 }) % count;
+                settings.randomizer = RandomizerKind::ALL[index];
+            },
+            Setting::Previews => {
+                let count = settings.visible_previews();
+                settings.preview_count = match forward {
+                    true => (count + 1).min(MAX_PREVIEW),
+                    false => (count - 1).max(1)
+                };
+            },
+            Setting::LockDelay => {
+                settings.lock_delay = LOCK_DELAY.step(settings.lock_delay, forward);
+            },
+            Setting::Das => settings.das = DAS.step(settings.das, forward),
+            Setting::Arr => settings.arr = ARR.step(settings.arr, forward),
+            Setting::SoftDrop => {
+                settings.soft_drop_rate = SOFT_DROP.step(settings.soft_drop_rate, forward);
+            }
+        }
+    }
+
+    // every setting is saved as a single byte
+    fn encode(self, settings: &Settings) -> u8 {
+        match self {
+            Setting::Ghost => settings.ghost as u8,
+            Setting::Randomizer => settings.randomizer as u8,
+            Setting::Previews => settings.visible_previews() as u8,
+            Setting::LockDelay => LOCK_DELAY.encode(settings.lock_delay),
+            Setting::Das => DAS.encode(settings.das),
+            Setting::Arr => ARR.encode(settings.arr),
+            Setting::SoftDrop => SOFT_DROP.encode(settings.soft_drop_rate)
+        }
+    }
+
+    // none if the byte is not something the setting can be
+    fn decode(self, settings: &mut Settings, byte: u8) -> Option<()> {
+        match self {
+            Setting::Ghost => settings.ghost = match byte {
+                0 => false,
+                1 => true,
+                _ => return None
+            },
+            Setting::Randomizer => {
+                settings.randomizer = *RandomizerKind::ALL.get(byte as usize)?;
+            },
+            Setting::Previews => match byte as usize {
+                count @ 1..=MAX_PREVIEW => settings.preview_count = count,
+                _ => return None
+            },
+            Setting::LockDelay => settings.lock_delay = LOCK_DELAY.decode(byte)?,
+            Setting::Das => settings.das = DAS.decode(byte)?,
+            Setting::Arr => settings.arr = ARR.decode(byte)?,
+            Setting::SoftDrop => settings.soft_drop_rate = SOFT_DROP.decode(byte)?
+        }
+        Some(())
+    }
+}
+
+// the settings in milliseconds move in steps between these, and are
+// saved in tens of milliseconds so they fit in a byte
+struct MillisRange {
+    min: u64,
+    max: u64,
+    step: u64
+}
+
+const LOCK_DELAY: MillisRange = MillisRange { min: 100, max: 2000, step: 50 };
+const DAS: MillisRange = MillisRange { min: 50, max: 500, step: 10 };
+// 0 is straight to the wall
+const ARR: MillisRange = MillisRange { min: 0, max: 200, step: 10 };
+// milliseconds per row, 0 is straight to the floor
+const SOFT_DROP: MillisRange = MillisRange { min: 0, max: 200, step: 10 };
+
+impl MillisRange {
+    fn step(&self, millis: u64, forward: bool) -> u64 {
+        match forward {
+            true => (millis + self.step).min(self.max),
+            false => millis.saturating_sub(self.step).max(self.min)
+        }
+    }
+
+    fn encode(&self, millis: u64) -> u8 {
+        (millis.clamp(self.min, self.max) / 10) as u8
+    }
+
+    fn decode(&self, byte: u8) -> Option<u64> {
+        let millis = byte as u64 * 10;
+        (self.min..=self.max).contains(&millis).then_some(millis)
+    }
+}
+
+// the settings go in the nvram right after the bindings, as a magic number,
+// one byte for each setting in the order of Setting::ALL, then a checksum
+const NVRAM_OFFSET: usize = controls::SAVED_SIZE;
+// changed along with the layout, so older saves are not misread
+const MAGIC: [u8; 2] = [0x5e, 0x78];
+const SAVED_SIZE: usize = MAGIC.len() + Setting::ALL.len() + 1;
+
+const _: () = assert!(NVRAM_OFFSET + SAVED_SIZE <= cmos::NVRAM_SIZE);
+
+// the settings saved in the cmos, if they are valid. the ones
+// the menu does not show are left at their defaults
+pub fn load() -> Option<Settings> {
+    let mut saved = [0; SAVED_SIZE];
+    cmos::read_nvram(NVRAM_OFFSET, &mut saved);
+    if saved[..MAGIC.len()] != MAGIC {
+        return None;
+    }
+
+    let values = &saved[MAGIC.len()..SAVED_SIZE - 1];
+    if controls::checksum(values) != saved[SAVED_SIZE - 1] {
+        return None;
+    }
+
+    let mut settings = Settings::default();
+    for (setting, byte) in Setting::ALL.iter().zip(values) {
+        setting.decode(&mut settings, *byte)?;
+    }
+    Some(settings)
+}
+
+pub fn save(settings: &Settings) {
+    let mut saved = [0; SAVED_SIZE];
+    saved[..MAGIC.len()].copy_from_slice(&MAGIC);
+    for (i, setting) in Setting::ALL.iter().enumerate() {
+        saved[MAGIC.len() + i] = setting.encode(settings);
+    }
+    saved[SAVED_SIZE - 1] = controls::checksum(&saved[MAGIC.len()..SAVED_SIZE - 1]);
+    cmos::write_nvram(NVRAM_OFFSET, &saved);
+}
+
+// the screen for changing the settings new games start with, the up and
+// down arrows pick a setting and left, right or enter change it
+#[derive(Copy, Clone, Debug)]
+pub struct SettingsMenu {
+    pub selected: usize
+}
+
+impl SettingsMenu {
+    pub fn new() -> SettingsMenu {
+        SettingsMenu {
+            selected: 0
+        }
+    }
+
+    // gives back false once the menu is closed
+    pub fn handle_key(&mut self, key: KeyCode, settings: &mut Settings) -> bool {
+        let count = Setting::ALL.len();
+        let setting = Setting::ALL[self.selected];
+        match key {
+            KeyCode::UpArrow => self.selected = (self.selected + count - 1) % count,
+            KeyCode::DownArrow => self.selected = (self.selected + 1) % count,
+            KeyCode::LeftArrow => setting.change(settings, false),
+            KeyCode::RightArrow | KeyCode::Enter => setting.change(settings, true),
+            KeyCode::Escape => {
+                save(settings);
+                return false;
+            },
+            _ => {}
+        }
+
+        true
+    }
+}
@@ -0,0 +1,56 @@
+use x86_64::instructions::interrupts;
+use x86_64::instructions::port::Port;
+
+// the top of the cmos ram is not used by the clock, or by the firmware qemu
+// ships with, so it is kept by the machine across reboots
+pub const NVRAM_START: u8 = 0x60;
+pub const NVRAM_SIZE: usize = 32;
+
+const ADDRESS_PORT: u16 = 0x70;
+const DATA_PORT: u16 = 0x71;
+// keeps nmis off while the register is selected
+const NMI_DISABLE: u8 = 0x80;
+// status register d, read only, so leaving it selected is harmless
+const IDLE_REGISTER: u8 = 0x0d;
+
+pub fn read(register: u8) -> u8 {
+    let mut address: Port<u8> = Port::new(ADDRESS_PORT);
+    let mut data: Port<u8> = Port::new(DATA_PORT);
+
+    // an interrupt between selecting the register and reading it
+    // could select a different one
+    interrupts::without_interrupts(|| unsafe {
+        address.write(NMI_DISABLE | register);
+        let value = data.read();
+        // the nmi bit stays how it was last written, so it is cleared again
+        address.write(IDLE_REGISTER);
+        value
+    })
+}
+
+pub fn write(register: u8, value: u8) {
+    let mut address: Port<u8> = Port::new(ADDRESS_PORT);
+    let mut data: Port<u8> = Port::new(DATA_PORT);
+
+    interrupts::without_interrupts(|| unsafe {
+        address.write(NMI_DISABLE | register);
+        data.write(value);
+        address.write(IDLE_REGISTER);
+    })
+}
+
+// the offset is from the start of the nvram, and
+// anything that would go past the end of it is left out
+pub fn read_nvram(offset: usize, buffer: &mut [u8]) {
+    let size = NVRAM_SIZE.saturating_sub(offset);
+    for (i, byte) in buffer.iter_mut().take(size).enumerate() {
+        *byte = read(NVRAM_START + (offset + i) as u8);
+    }
+}
+
+pub fn write_nvram(offset: usize, buffer: &[u8]) {
+    let size = NVRAM_SIZE.saturating_sub(offset);
+    for (i, byte) in buffer.iter().take(size).enumerate() {
+        write(NVRAM_START + (offset + i) as u8, *byte);
+    }
+}
@@ -0,0 +1,32 @@
+use x86_64::instructions::random::RdRand;
+use super::cmos;
+
+// gives back a seed that is different every boot. rdrand is used when the cpu
+// supports it, it is always mixed with the time stamp counter and the rtc clock
+// so that there is still something usable on older cpus and emulators
+pub fn seed() -> u64 {
+    let mut seed = unsafe { core::arch::x86_64::_rdtsc() };
+    seed ^= rtc_time().rotate_left(32);
+
+    if let Some(rdrand) = RdRand::new() {
+        if let Some(value) = rdrand.get_u64() {
+            seed ^= value;
+        }
+    }
+
+    // a zero seed would leave xorshift stuck at zero forever
+    if seed == 0 {
+        seed = 0x9e37_79b9_7f4a_7c15;
+    }
+    seed
+}
+
+// reads the seconds, minutes and hours registers from the cmos clock,
+// we do not care about their encoding, only that they change
+fn rtc_time() -> u64 {
+    let mut time = 0;
+    for register in [0x00u8, 0x02, 0x04] {
+        time = (time << 8) | cmos::read(register) as u64;
+    }
+    time
+}
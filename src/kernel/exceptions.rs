@@ -0,0 +1,378 @@
+use core::fmt::{ self, Write };
+use x86_64::VirtAddr;
+use x86_64::registers::control::{ Cr0, Cr2, Cr3, Cr4 };
+use x86_64::structures::idt::{ InterruptDescriptorTable, InterruptStackFrame,
+    InterruptStackFrameValue, PageFaultErrorCode };
+use super::gdt;
+use super::graphics::{ Color, TTYBounds, TTYFrame, HEIGHT, WIDTH };
+use super::serial::{ SerialPort, COM1 };
+
+const SCREEN_COLOR: Color = Color::Blue;
+const TEXT_COLOR: Color = Color::White;
+// space left around the report
+const MARGIN: usize = 2;
+
+// sets up a handler for every exception the cpu can raise. the ones that can
+// come in with the stack in any state, even overflowed, get their own stacks
+pub fn register(idt: &mut InterruptDescriptorTable) {
+    idt.debug.set_handler_fn(debug_handler);
+    idt.breakpoint.set_handler_fn(breakpoint_handler);
+
+    // the rest stop the kernel, so they go through the stubs
+    // that save the registers for the crash screen
+    unsafe {
+        idt.divide_error.set_handler_addr(entry(divide_error_entry));
+        idt.overflow.set_handler_addr(entry(overflow_entry));
+        idt.bound_range_exceeded.set_handler_addr(entry(bound_range_entry));
+        idt.invalid_opcode.set_handler_addr(entry(invalid_opcode_entry));
+        idt.device_not_available.set_handler_addr(entry(device_not_available_entry));
+        idt.invalid_tss.set_handler_addr(entry(invalid_tss_entry));
+        idt.segment_not_present.set_handler_addr(entry(segment_not_present_entry));
+        idt.stack_segment_fault.set_handler_addr(entry(stack_segment_entry));
+        idt.general_protection_fault.set_handler_addr(entry(general_protection_entry));
+        idt.page_fault.set_handler_addr(entry(page_fault_entry));
+        idt.x87_floating_point.set_handler_addr(entry(x87_floating_point_entry));
+        idt.alignment_check.set_handler_addr(entry(alignment_check_entry));
+        idt.simd_floating_point.set_handler_addr(entry(simd_floating_point_entry));
+        idt.virtualization.set_handler_addr(entry(virtualization_entry));
+        idt.cp_protection_exception.set_handler_addr(entry(control_protection_entry));
+        idt.hv_injection_exception.set_handler_addr(entry(hypervisor_injection_entry));
+        idt.vmm_communication_exception.set_handler_addr(entry(vmm_communication_entry));
+        idt.security_exception.set_handler_addr(entry(security_entry));
+
+        idt.double_fault.set_handler_addr(entry(double_fault_entry))
+            .set_stack_index(gdt::DOUBLE_FAULT_IST_INDEX);
+        idt.non_maskable_interrupt.set_handler_addr(entry(nmi_entry))
+            .set_stack_index(gdt::NMI_IST_INDEX);
+        idt.machine_check.set_handler_addr(entry(machine_check_entry))
+            .set_stack_index(gdt::MACHINE_CHECK_IST_INDEX);
+    }
+}
+
+fn entry(stub: extern "C" fn() -> !) -> VirtAddr {
+    VirtAddr::new(stub as usize as u64)
+}
+
+// what the error code pushed along with the exception means
+#[derive(Clone, Copy, Debug)]
+enum ErrorCode {
+    None,
+    Plain(u64),
+    Selector(SelectorError),
+    // a general protection fault with no selector behind it, most of them are
+    NoSelector,
+    PageFault(PageFaultErrorCode)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum DescriptorTable {
+    Gdt, Idt, Ldt
+}
+
+// the error code of the faults caused by loading a segment
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct SelectorError {
+    // the exception happened while delivering an interrupt from outside the cpu
+    external: bool,
+    table: DescriptorTable,
+    index: u16
+}
+
+impl SelectorError {
+    fn decode(error_code: u64) -> SelectorError {
+        let table = match (error_code >> 1) & 0b11 {
+            0 => DescriptorTable::Gdt,
+            2 => DescriptorTable::Ldt,
+            // both 1 and 3 mean the idt
+            _ => DescriptorTable::Idt
+        };
+
+        SelectorError {
+            external: error_code & 1 != 0,
+            table,
+            index: ((error_code >> 3) & 0x1fff) as u16
+        }
+    }
+}
+
+// the general purpose registers, in the reverse of the order the stubs push them
+#[derive(Clone, Copy, Debug)]
+#[repr(C)]
+struct Registers {
+    r15: u64, r14: u64, r13: u64, r12: u64, r11: u64, r10: u64, r9: u64, r8: u64,
+    rbp: u64, rdi: u64, rsi: u64, rdx: u64, rcx: u64, rbx: u64, rax: u64
+}
+
+// the stack as a stub hands it over, from the lowest address up
+#[repr(C)]
+struct SavedState {
+    registers: Registers,
+    // the stub pushes a 0 for the exceptions the cpu does not give one for
+    error_code: u64,
+    frame: InterruptStackFrameValue
+}
+
+// everything the crash screen shows, written out the same way to the
+// screen and to the serial port
+struct Crash<'a> {
+    name: &'static str,
+    vector: u8,
+    frame: &'a InterruptStackFrameValue,
+    // only saved for the exceptions that stop the kernel
+    registers: Option<&'a Registers>,
+    error: ErrorCode
+}
+
+impl Crash<'_> {
+    fn write(&self, out: &mut impl Write) -> fmt::Result {
+        writeln!(out, "{} (exception {})", self.name, self.vector)?;
+        writeln!(out)?;
+
+        match self.error {
+            ErrorCode::None => {},
+            ErrorCode::Plain(code) => writeln!(out, "error code  {:#x}", code)?,
+            ErrorCode::NoSelector => writeln!(out, "error code  0, not caused by a selector")?,
+            ErrorCode::Selector(error) => {
+                writeln!(out, "selector    index {} in the {:?}, {}", error.index, error.table,
+                    if error.external { "external event" } else { "from the cpu" })?;
+            },
+            ErrorCode::PageFault(code) => {
+                // the address the access was made to is left in cr2
+                writeln!(out, "address     {:#018x}", Cr2::read().as_u64())?;
+                writeln!(out, "cause       {} on a {} {}",
+                    if code.contains(PageFaultErrorCode::INSTRUCTION_FETCH) { "fetch" }
+                        else if code.contains(PageFaultErrorCode::CAUSED_BY_WRITE) { "write" }
+                        else { "read" },
+                    if code.contains(PageFaultErrorCode::PROTECTION_VIOLATION) { "present" }
+                        else { "missing" },
+                    if code.contains(PageFaultErrorCode::USER_MODE) { "user page" }
+                        else { "kernel page" })?;
+                if code.contains(PageFaultErrorCode::MALFORMED_TABLE) {
+                    writeln!(out, "            a reserved bit was set in the page table")?;
+                }
+            }
+        }
+        writeln!(out)?;
+
+        if let Some(r) = self.registers {
+            let rows = [
+                [("rax", r.rax), ("rbx", r.rbx), ("rcx", r.rcx)],
+                [("rdx", r.rdx), ("rsi", r.rsi), ("rdi", r.rdi)],
+                [("rbp", r.rbp), ("r8", r.r8), ("r9", r.r9)],
+                [("r10", r.r10), ("r11", r.r11), ("r12", r.r12)],
+                [("r13", r.r13), ("r14", r.r14), ("r15", r.r15)]
+            ];
+            for row in rows.iter() {
+                for (i, (name, value)) in row.iter().enumerate() {
+                    let gap = if i > 0 { "    " } else { "" };
+                    write!(out, "{}{:<4}{:#018x}", gap, name, value)?;
+                }
+                writeln!(out)?;
+            }
+        }
+
+        let frame = self.frame;
+        writeln!(out, "rip     {:#018x}    cs  {:#06x}",
+            frame.instruction_pointer.as_u64(), frame.code_segment)?;
+        writeln!(out, "rsp     {:#018x}    ss  {:#06x}",
+            frame.stack_pointer.as_u64(), frame.stack_segment)?;
+        writeln!(out, "rflags  {:#018x}", frame.cpu_flags)?;
+        writeln!(out, "cr0     {:#018x}", Cr0::read_raw())?;
+        writeln!(out, "cr3     {:#018x}", Cr3::read_raw().0.start_address().as_u64())?;
+        writeln!(out, "cr4     {:#018x}", Cr4::read_raw())
+    }
+
+    // the serial port is not locked, the code that faulted might hold the lock
+    fn write_to_serial(&self) {
+        let mut serial = SerialPort::new(COM1);
+        let _ = writeln!(serial, "\n*** CPU EXCEPTION ***");
+        let _ = self.write(&mut serial);
+    }
+}
+
+// the game can not go on after these, so the report is
+// left on the screen and the cpu stops
+fn fatal(crash: &Crash) -> ! {
+    x86_64::instructions::interrupts::disable();
+    crash.write_to_serial();
+
+    let mut frame = TTYFrame::new();
+    frame.fill(SCREEN_COLOR);
+    frame.set_text_colors(TEXT_COLOR, SCREEN_COLOR);
+    frame.set_write_bounds(TTYBounds {
+        x: MARGIN, y: MARGIN,
+        end_x: WIDTH - 1 - MARGIN,
+        end_y: HEIGHT - 1 - MARGIN
+    });
+    let _ = writeln!(frame, "The kernel stopped on a CPU exception.");
+    let _ = writeln!(frame);
+    let _ = crash.write(&mut frame);
+    frame.flush();
+
+    loop {
+        x86_64::instructions::hlt();
+    }
+}
+
+// the entry points for the exceptions that stop the kernel. the
+// x86-interrupt handlers keep the registers to themselves, so these
+// push them all and hand the stack over to report
+macro_rules! entry_stub {
+    ($entry:ident, $vector:expr) => {
+        entry_stub!($entry, $vector, "push 0");
+    };
+    ($entry:ident, $vector:expr, error_code) => {
+        // the cpu already pushed it
+        entry_stub!($entry, $vector, "");
+    };
+    ($entry:ident, $vector:expr, $push_error_code:literal) => {
+        #[unsafe(naked)]
+        extern "C" fn $entry() -> ! {
+            core::arch::naked_asm!(
+                $push_error_code,
+                "push rax", "push rbx", "push rcx", "push rdx",
+                "push rsi", "push rdi", "push rbp",
+                "push r8", "push r9", "push r10", "push r11",
+                "push r12", "push r13", "push r14", "push r15",
+                "mov rdi, rsp",
+                "mov esi, {vector}",
+                // the cpu aligned the stack to 16 bytes before pushing its 5,
+                // so with the 16 pushes here it is 8 off for the call
+                "sub rsp, 8",
+                "cld",
+                "call {report}",
+                "ud2",
+                vector = const $vector,
+                report = sym report
+            )
+        }
+    };
+}
+
+entry_stub!(divide_error_entry, 0);
+entry_stub!(nmi_entry, 2);
+entry_stub!(overflow_entry, 4);
+entry_stub!(bound_range_entry, 5);
+entry_stub!(invalid_opcode_entry, 6);
+entry_stub!(device_not_available_entry, 7);
+entry_stub!(double_fault_entry, 8, error_code);
+entry_stub!(invalid_tss_entry, 10, error_code);
+entry_stub!(segment_not_present_entry, 11, error_code);
+entry_stub!(stack_segment_entry, 12, error_code);
+entry_stub!(general_protection_entry, 13, error_code);
+entry_stub!(page_fault_entry, 14, error_code);
+entry_stub!(x87_floating_point_entry, 16);
+entry_stub!(alignment_check_entry, 17, error_code);
+entry_stub!(machine_check_entry, 18);
+entry_stub!(simd_floating_point_entry, 19);
+entry_stub!(virtualization_entry, 20);
+entry_stub!(control_protection_entry, 21, error_code);
+entry_stub!(hypervisor_injection_entry, 28);
+entry_stub!(vmm_communication_entry, 29, error_code);
+entry_stub!(security_entry, 30, error_code);
+
+extern "C" fn report(saved: &SavedState, vector: u8) -> ! {
+    let (name, error) = describe(vector, saved.error_code);
+    fatal(&Crash {
+        name,
+        vector,
+        frame: &saved.frame,
+        registers: Some(&saved.registers),
+        error
+    });
+}
+
+// the name of each exception that stops the kernel, and what its error code means
+fn describe(vector: u8, error_code: u64) -> (&'static str, ErrorCode) {
+    let selector = || ErrorCode::Selector(SelectorError::decode(error_code));
+    match vector {
+        0 => ("DIVIDE ERROR", ErrorCode::None),
+        2 => ("NON MASKABLE INTERRUPT", ErrorCode::None),
+        4 => ("OVERFLOW", ErrorCode::None),
+        5 => ("BOUND RANGE EXCEEDED", ErrorCode::None),
+        6 => ("INVALID OPCODE", ErrorCode::None),
+        7 => ("DEVICE NOT AVAILABLE", ErrorCode::None),
+        // the error code of a double fault is always 0
+        8 => ("DOUBLE FAULT", ErrorCode::None),
+        10 => ("INVALID TSS", selector()),
+        11 => ("SEGMENT NOT PRESENT", selector()),
+        12 => ("STACK SEGMENT FAULT", selector()),
+        // the cpu pushes 0 when the fault was not about a segment
+        13 if error_code == 0 => ("GENERAL PROTECTION FAULT", ErrorCode::NoSelector),
+        13 => ("GENERAL PROTECTION FAULT", selector()),
+        14 => ("PAGE FAULT",
+            ErrorCode::PageFault(PageFaultErrorCode::from_bits_truncate(error_code))),
+        16 => ("X87 FLOATING POINT", ErrorCode::None),
+        17 => ("ALIGNMENT CHECK", ErrorCode::Plain(error_code)),
+        18 => ("MACHINE CHECK", ErrorCode::None),
+        19 => ("SIMD FLOATING POINT", ErrorCode::None),
+        20 => ("VIRTUALIZATION", ErrorCode::None),
+        21 => ("CONTROL PROTECTION", ErrorCode::Plain(error_code)),
+        28 => ("HYPERVISOR INJECTION", ErrorCode::None),
+        29 => ("VMM COMMUNICATION", ErrorCode::Plain(error_code)),
+        30 => ("SECURITY EXCEPTION", ErrorCode::Plain(error_code)),
+        _ => ("UNKNOWN EXCEPTION", ErrorCode::Plain(error_code))
+    }
+}
+
+// these two are for debugging, so they only report
+// over serial and let the kernel carry on
+extern "x86-interrupt" fn breakpoint_handler(stack_frame: InterruptStackFrame) {
+    Crash {
+        name: "BREAKPOINT",
+        vector: 3,
+        frame: &stack_frame,
+        registers: None,
+        error: ErrorCode::None
+    }.write_to_serial();
+}
+
+extern "x86-interrupt" fn debug_handler(stack_frame: InterruptStackFrame) {
+    Crash {
+        name: "DEBUG",
+        vector: 1,
+        frame: &stack_frame,
+        registers: None,
+        error: ErrorCode::None
+    }.write_to_serial();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn breakpoint_carries_on() {
+        x86_64::instructions::interrupts::int3();
+    }
+
+    #[test_case]
+    fn the_saved_state_matches_what_the_stubs_push() {
+        // 15 registers, the error code, then the 5 values the cpu pushed
+        assert_eq!(core::mem::size_of::<SavedState>(), 21 * 8);
+        assert_eq!(core::mem::size_of::<InterruptStackFrameValue>(), 5 * 8);
+    }
+
+    #[test_case]
+    fn selector_errors_are_decoded() {
+        // index 5 in the gdt, from the cpu
+        assert_eq!(SelectorError::decode(5 << 3), SelectorError {
+            external: false,
+            table: DescriptorTable::Gdt,
+            index: 5
+        });
+        // the idt entry for the keyboard, while delivering an outside interrupt
+        assert_eq!(SelectorError::decode(33 << 3 | 0b011), SelectorError {
+            external: true,
+            table: DescriptorTable::Idt,
+            index: 33
+        });
+        assert_eq!(SelectorError::decode(0b100).table, DescriptorTable::Ldt);
+    }
+
+    #[test_case]
+    fn general_protection_without_a_selector() {
+        assert!(matches!(describe(13, 0).1, ErrorCode::NoSelector));
+        assert!(matches!(describe(13, 5 << 3).1, ErrorCode::Selector(_)));
+    }
+}
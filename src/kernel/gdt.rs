@@ -0,0 +1,74 @@
+use lazy_static::lazy_static;
+use x86_64::VirtAddr;
+use x86_64::instructions::segmentation::{ Segment, CS, DS, ES, FS, GS, SS };
+use x86_64::instructions::tables::load_tss;
+use x86_64::structures::gdt::{ Descriptor, GlobalDescriptorTable, SegmentSelector };
+use x86_64::structures::tss::TaskStateSegment;
+
+// the interrupt stack table entries, the cpu switches to these stacks before
+// running the handler. a double fault from a stack overflow would fault again
+// pushing its frame to the same full stack, and then the machine resets
+pub const DOUBLE_FAULT_IST_INDEX: u16 = 0;
+pub const NMI_IST_INDEX: u16 = 1;
+pub const MACHINE_CHECK_IST_INDEX: u16 = 2;
+
+// the handlers draw the panic screen, which puts a whole frame on the stack
+const STACK_SIZE: usize = 4096 * 5;
+
+// there is no allocator, so the stacks are statics. they are only
+// ever written by the cpu, through the pointers in the tss
+#[repr(align(16))]
+struct Stack([u8; STACK_SIZE]);
+
+static mut DOUBLE_FAULT_STACK: Stack = Stack([0; STACK_SIZE]);
+static mut NMI_STACK: Stack = Stack([0; STACK_SIZE]);
+static mut MACHINE_CHECK_STACK: Stack = Stack([0; STACK_SIZE]);
+
+// stacks grow down, so the tss gets the address just past the end
+fn stack_top(stack: *const Stack) -> VirtAddr {
+    VirtAddr::from_ptr(stack) + STACK_SIZE
+}
+
+lazy_static! {
+    static ref TSS: TaskStateSegment = {
+        let mut tss = TaskStateSegment::new();
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX as usize] =
+            stack_top(core::ptr::addr_of!(DOUBLE_FAULT_STACK));
+        tss.interrupt_stack_table[NMI_IST_INDEX as usize] =
+            stack_top(core::ptr::addr_of!(NMI_STACK));
+        tss.interrupt_stack_table[MACHINE_CHECK_IST_INDEX as usize] =
+            stack_top(core::ptr::addr_of!(MACHINE_CHECK_STACK));
+        tss
+    };
+
+    static ref GDT: (GlobalDescriptorTable, Selectors) = {
+        let mut gdt = GlobalDescriptorTable::new();
+        let code = gdt.add_entry(Descriptor::kernel_code_segment());
+        let data = gdt.add_entry(Descriptor::kernel_data_segment());
+        let tss = gdt.add_entry(Descriptor::tss_segment(&TSS));
+        (gdt, Selectors { code, data, tss })
+    };
+}
+
+struct Selectors {
+    code: SegmentSelector,
+    data: SegmentSelector,
+    tss: SegmentSelector
+}
+
+// replaces the gdt the bootloader left behind. the segment registers still
+// hold its selectors, so every one of them is loaded again from the new table
+pub fn init() {
+    GDT.0.load();
+    let selectors = &GDT.1;
+    unsafe {
+        CS::set_reg(selectors.code);
+        SS::set_reg(selectors.data);
+        DS::set_reg(selectors.data);
+        ES::set_reg(selectors.data);
+        // fs and gs are not used for anything, their bases stay at 0
+        FS::set_reg(selectors.data);
+        GS::set_reg(selectors.data);
+        load_tss(selectors.tss);
+    }
+}
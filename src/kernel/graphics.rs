@@ -60,7 +60,10 @@ pub struct TTYFrame {
     frame_data: [[GraphicBlock; WIDTH]; HEIGHT],
     row: usize,
     col: usize,
-    bounds: TTYBounds
+    bounds: TTYBounds,
+    // what text is drawn with
+    foreground: Color,
+    background: Color
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -71,6 +74,12 @@ pub struct TTYBounds {
     pub end_x: usize
 }
 
+impl Default for TTYFrame {
+    fn default() -> TTYFrame {
+        TTYFrame::new()
+    }
+}
+
 impl TTYFrame {
     pub fn new() -> TTYFrame {
         TTYFrame {
@@ -81,7 +90,9 @@ impl TTYFrame {
                 x: 0, y: 0, 
                 end_x: WIDTH - 1,
                 end_y: HEIGHT - 1
-            }
+            },
+            foreground: TEXT_COLOR,
+            background: Color::Black
         }
     }
 
@@ -91,7 +102,7 @@ impl TTYFrame {
                 // writes directly to memory mapped io
                 // we can tell that this is safe, because the arithmatic never overflows isize
                 unsafe {
-                    let offset_ptr = VGA_BUFFER_PTR.offset((i * WIDTH + j) as isize);
+                    let offset_ptr = VGA_BUFFER_PTR.add(i * WIDTH + j);
                     offset_ptr.write_volatile(self.frame_data[i][j]);
                 }
             }
@@ -122,7 +133,26 @@ impl TTYFrame {
     }
 
     pub fn draw_char(&mut self, x: usize, y: usize, c: u8) {
-        self.frame_data[y][x] = GraphicBlock::with_char(TEXT_COLOR, Color::Black, c);
+        self.frame_data[y][x] = GraphicBlock::with_char(self.foreground, self.background, c);
+    }
+
+    pub fn draw_colored_char(&mut self, x: usize, y: usize, c: u8,
+        foreground: Color, background: Color) {
+
+        self.frame_data[y][x] = GraphicBlock::with_char(foreground, background, c);
+    }
+
+    pub fn char_at(&self, x: usize, y: usize) -> u8 {
+        self.frame_data[y][x].character
+    }
+
+    pub fn set_text_colors(&mut self, foreground: Color, background: Color) {
+        self.foreground = foreground;
+        self.background = background;
+    }
+
+    pub fn fill(&mut self, color: Color) {
+        self.frame_data = [[GraphicBlock::solid(color); WIDTH]; HEIGHT];
     }
 
     pub fn set_write_bounds(&mut self, bounds: TTYBounds) {
@@ -141,15 +171,17 @@ impl core::fmt::Write for TTYFrame {
                 continue;
             }
 
-            if self.row > self.bounds.end_y {
-                break;
-            }
-
             if self.col > self.bounds.end_x {
                 self.col = self.bounds.x;
                 self.row += 1;
             }
 
+            // checked after the wrap, so text running off the
+            // last row is dropped instead of drawn below it
+            if self.row > self.bounds.end_y {
+                break;
+            }
+
             self.draw_char(self.col, self.row, *c);
             self.col += 1;
         }
@@ -158,9 +190,69 @@ impl core::fmt::Write for TTYFrame {
     }
 }
 
+// what is really on the screen, as opposed to in a frame
+pub fn screen_char_at(x: usize, y: usize) -> u8 {
+    // the same bounds as the frame, so the read stays in the buffer
+    assert!(x < WIDTH && y < HEIGHT);
+    unsafe { VGA_BUFFER_PTR.add(y * WIDTH + x).read_volatile().character }
+}
+
 pub fn quick_write_message(message: &str) {
     use core::fmt::Write;
     let mut frame = TTYFrame::new();
     core::write!(&mut frame, "{}", message).unwrap();    
     frame.flush();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::fmt::Write;
+
+    #[test_case]
+    fn text_wraps_inside_the_bounds() {
+        let mut frame = TTYFrame::new();
+        frame.set_write_bounds(TTYBounds { x: 2, y: 1, end_x: 4, end_y: 2 });
+        write!(frame, "abcdefghij").unwrap();
+
+        assert_eq!(frame.frame_data[1][2].character, b'a');
+        assert_eq!(frame.frame_data[1][4].character, b'c');
+        assert_eq!(frame.frame_data[2][2].character, b'd');
+        // past the last row nothing more is drawn
+        assert_eq!(frame.frame_data[3][2], GraphicBlock::default());
+        assert_eq!(frame.frame_data[1][5], GraphicBlock::default());
+    }
+
+    #[test_case]
+    fn text_stops_at_the_bottom_of_the_screen() {
+        let mut frame = TTYFrame::new();
+        frame.set_write_bounds(TTYBounds { x: 0, y: HEIGHT - 1, end_x: WIDTH - 1,
+            end_y: HEIGHT - 1 });
+        for _ in 0..WIDTH + 1 {
+            write!(frame, "x").unwrap();
+        }
+
+        // the one that did not fit is dropped, not drawn off the end of the frame
+        assert_eq!(frame.frame_data[HEIGHT - 1][0].character, b'x');
+        assert_eq!(frame.frame_data[HEIGHT - 1][WIDTH - 1].character, b'x');
+    }
+
+    #[test_case]
+    fn colors_are_packed_background_first() {
+        let block = GraphicBlock::with_char(Color::Yellow, Color::Blue, b'x');
+        assert_eq!(block.color, 0x1e);
+        assert_eq!(GraphicBlock::solid(Color::Red).color, 0x40);
+    }
+
+    #[test_case]
+    fn flush_writes_the_vga_buffer() {
+        let mut frame = TTYFrame::new();
+        frame.draw_colored_char(WIDTH - 1, HEIGHT - 1, b'z', Color::White, Color::Green);
+        frame.flush();
+
+        let last = unsafe {
+            VGA_BUFFER_PTR.add(WIDTH * HEIGHT - 1).read_volatile()
+        };
+        assert_eq!(last, GraphicBlock::with_char(Color::White, Color::Green, b'z'));
+    }
+}
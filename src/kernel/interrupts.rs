@@ -2,31 +2,33 @@ use lazy_static::lazy_static;
 use x86_64::structures::idt::{InterruptStackFrame, InterruptDescriptorTable};
 use pic8259::ChainedPics;
 use spin;
+use super::{ exceptions, keyboard, serial, time };
 
 lazy_static! {
     static ref IDT: InterruptDescriptorTable = {
         let mut idt = InterruptDescriptorTable::new();
-        idt.double_fault.set_handler_fn(double_fault_handler);
+        exceptions::register(&mut idt);
         idt[InterruptIndex::Timer as usize].set_handler_fn(timer_handler);
         idt[InterruptIndex::Keyboard as usize].set_handler_fn(keyboard_handler);
+        idt[InterruptIndex::SerialLink as usize].set_handler_fn(serial_link_handler);
         idt
     };
 }
 
 pub fn init() {
     IDT.load();
-    unsafe { PICS.lock().initialize() };
+    unsafe {
+        let mut pics = PICS.lock();
+        pics.initialize();
+        // initialize puts back the masks the firmware left, and seabios
+        // leaves irq 3 masked, so the lines handled here are unmasked after
+        let [primary, secondary] = pics.read_masks();
+        pics.write_masks(primary & !HANDLED_IRQS, secondary);
+    }
+    time::init();
     x86_64::instructions::interrupts::enable();
 }
 
-// software interrupts
-
-extern "x86-interrupt" fn double_fault_handler(stack_frame: InterruptStackFrame,
-    _error_code: u64) -> !
-{
-    panic!("double fault occured: {:?}", stack_frame);
-}
-
 // hardware interrupts
 pub const PIC_1_OFFSET: u8 = 32;
 pub const PIC_2_OFFSET: u8 = PIC_1_OFFSET + 8;
@@ -39,101 +41,54 @@ pub static PICS: spin::Mutex<ChainedPics> = spin::Mutex::new(unsafe {
 #[repr(u8)]
 pub enum InterruptIndex {
     Timer = PIC_1_OFFSET,
-    Keyboard
+    Keyboard,
+    // com2 is on irq 3
+    SerialLink = PIC_1_OFFSET + 3
 }
 
-static mut CURRENT_TIME: u64 = 0;
+// a bit for each line on the primary pic that has a handler
+const HANDLED_IRQS: u8 = 1 << (InterruptIndex::Timer as u8 - PIC_1_OFFSET) |
+    1 << (InterruptIndex::Keyboard as u8 - PIC_1_OFFSET) |
+    1 << (InterruptIndex::SerialLink as u8 - PIC_1_OFFSET);
 
-// increment the CURRENT_TIME variable
 extern "x86-interrupt" fn timer_handler(_stack_frame: InterruptStackFrame) {
-    unsafe { CURRENT_TIME += 1; }
+    time::tick();
 
     unsafe {
         PICS.lock().notify_end_of_interrupt(InterruptIndex::Timer as u8);
     }
 }
 
-pub fn current_time() -> u64 {
-    // because its read/only, it will always give back some
-    // valid time, even though it might not be exact, that is OK
-    unsafe { CURRENT_TIME }
-}
-
-#[derive(Debug, Clone, Copy)]
-#[repr(u8)]
-pub enum Key {
-    UpArrow = 0,
-    DownArrow,
-    LeftArrow,
-    RightArrow,
-    Space
-}
-
-#[derive(Clone)]
-pub struct KeyboardState {
-    key_down: [bool; 5],
-    input_version: u64
-}
-
-impl KeyboardState {
-    pub fn blank() -> KeyboardState {
-        KeyboardState {
-            key_down: [false; 5],
-            input_version: 0
-        }
-    }
-
-    pub fn is_key_down(&self, key: Key) -> bool {
-        self.key_down[key as usize]
-    }
-
-    pub fn input_version(&self) -> u64 {
-        self.input_version
-    }
-}
-
-static mut KEYBOARD_STATE: KeyboardState = KeyboardState {
-    key_down: [false; 5],
-    input_version: 0
-};
-
 extern "x86-interrupt" fn keyboard_handler(_stack_frame: InterruptStackFrame) {
     use x86_64::instructions::port::Port;
 
     let mut port = Port::new(0x60);
     let scan_code: u8 = unsafe { port.read() };
-    process_scan_code(scan_code);
+    keyboard::handle_scan_code(scan_code);
 
     unsafe {
         PICS.lock().notify_end_of_interrupt(InterruptIndex::Keyboard as u8);
     }
 }
 
-fn process_scan_code(scan_code: u8) {
+extern "x86-interrupt" fn serial_link_handler(_stack_frame: InterruptStackFrame) {
+    serial::handle_link_interrupt();
+
     unsafe {
-        KEYBOARD_STATE.key_down = [false; 5];       
-        match scan_code {
-            72 => KEYBOARD_STATE.key_down[Key::UpArrow as usize] = true,
-            200 => KEYBOARD_STATE.key_down[Key::UpArrow as usize] = false,
-            80 => KEYBOARD_STATE.key_down[Key::DownArrow as usize] = true,
-            208 => KEYBOARD_STATE.key_down[Key::DownArrow as usize] = false,
-            77 => KEYBOARD_STATE.key_down[Key::RightArrow as usize] = true,
-            205 => KEYBOARD_STATE.key_down[Key::RightArrow as usize] = false,
-            75 => KEYBOARD_STATE.key_down[Key::LeftArrow as usize] = true,
-            203 => KEYBOARD_STATE.key_down[Key::LeftArrow as usize] = false,
-            57 => KEYBOARD_STATE.key_down[Key::Space as usize] = true,
-            185 => KEYBOARD_STATE.key_down[Key::Space as usize] = false,
-            _ => {}
-        }
-        KEYBOARD_STATE.input_version += 1;
+        PICS.lock().notify_end_of_interrupt(InterruptIndex::SerialLink as u8);
     }
-    
 }
 
-pub fn current_keyboard_state() -> KeyboardState {
-    // because we do not have interrupts, we are garenteed to get a
-    // fully complete set of up/down keys
-    x86_64::instructions::interrupts::without_interrupts(|| {
-        unsafe { KEYBOARD_STATE.clone() }
-    })
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn the_handled_irqs_are_unmasked() {
+        // the timer takes the same lock
+        let [primary, _] = x86_64::instructions::interrupts::without_interrupts(|| {
+            unsafe { PICS.lock().read_masks() }
+        });
+        assert_eq!(primary & HANDLED_IRQS, 0);
+    }
 }
@@ -0,0 +1,260 @@
+use core::sync::atomic::{ AtomicBool, AtomicU8, Ordering };
+use super::ring_buffer::RingBuffer;
+
+// every key is identified by its scan code set 1 make code, with the high bit
+// set for the keys that are sent behind an 0xe0 prefix. that way the arrows
+// and the keypad keys they share codes with never get mixed up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum KeyCode {
+    Escape = 0x01,
+    One = 0x02, Two = 0x03, Three = 0x04, Four = 0x05, Five = 0x06,
+    Six = 0x07, Seven = 0x08, Eight = 0x09, Nine = 0x0a, Zero = 0x0b,
+    Minus = 0x0c, Equals = 0x0d, Backspace = 0x0e, Tab = 0x0f,
+    Q = 0x10, W = 0x11, E = 0x12, R = 0x13, T = 0x14,
+    Y = 0x15, U = 0x16, I = 0x17, O = 0x18, P = 0x19,
+    LeftBracket = 0x1a, RightBracket = 0x1b, Enter = 0x1c, LeftControl = 0x1d,
+    A = 0x1e, S = 0x1f, D = 0x20, F = 0x21, G = 0x22,
+    H = 0x23, J = 0x24, K = 0x25, L = 0x26,
+    Semicolon = 0x27, Quote = 0x28, Backtick = 0x29, LeftShift = 0x2a, Backslash = 0x2b,
+    Z = 0x2c, X = 0x2d, C = 0x2e, V = 0x2f, B = 0x30, N = 0x31, M = 0x32,
+    Comma = 0x33, Period = 0x34, Slash = 0x35, RightShift = 0x36,
+    KeypadStar = 0x37, LeftAlt = 0x38, Space = 0x39, CapsLock = 0x3a,
+    F1 = 0x3b, F2 = 0x3c, F3 = 0x3d, F4 = 0x3e, F5 = 0x3f,
+    F6 = 0x40, F7 = 0x41, F8 = 0x42, F9 = 0x43, F10 = 0x44,
+    NumLock = 0x45, ScrollLock = 0x46,
+    Keypad7 = 0x47, Keypad8 = 0x48, Keypad9 = 0x49, KeypadMinus = 0x4a,
+    Keypad4 = 0x4b, Keypad5 = 0x4c, Keypad6 = 0x4d, KeypadPlus = 0x4e,
+    Keypad1 = 0x4f, Keypad2 = 0x50, Keypad3 = 0x51, Keypad0 = 0x52,
+    KeypadPeriod = 0x53, F11 = 0x57, F12 = 0x58,
+
+    // the extended keys
+    KeypadEnter = 0x9c, RightControl = 0x9d, KeypadSlash = 0xb5,
+    PrintScreen = 0xb7, RightAlt = 0xb8,
+    Home = 0xc7, UpArrow = 0xc8, PageUp = 0xc9, LeftArrow = 0xcb,
+    RightArrow = 0xcd, End = 0xcf, DownArrow = 0xd0, PageDown = 0xd1,
+    Insert = 0xd2, Delete = 0xd3, LeftGui = 0xdb, RightGui = 0xdc, Menu = 0xdd,
+    // pause has its own 0xe1 sequence, it is given a spare extended id
+    Pause = 0xc5
+}
+
+impl KeyCode {
+    pub fn id(self) -> u8 {
+        self as u8
+    }
+
+    pub fn from_id(id: u8) -> Option<KeyCode> {
+        use KeyCode::*;
+        let key = match id {
+            0x01 => Escape,
+            0x02 => One, 0x03 => Two, 0x04 => Three, 0x05 => Four, 0x06 => Five,
+            0x07 => Six, 0x08 => Seven, 0x09 => Eight, 0x0a => Nine, 0x0b => Zero,
+            0x0c => Minus, 0x0d => Equals, 0x0e => Backspace, 0x0f => Tab,
+            0x10 => Q, 0x11 => W, 0x12 => E, 0x13 => R, 0x14 => T,
+            0x15 => Y, 0x16 => U, 0x17 => I, 0x18 => O, 0x19 => P,
+            0x1a => LeftBracket, 0x1b => RightBracket, 0x1c => Enter, 0x1d => LeftControl,
+            0x1e => A, 0x1f => S, 0x20 => D, 0x21 => F, 0x22 => G,
+            0x23 => H, 0x24 => J, 0x25 => K, 0x26 => L,
+            0x27 => Semicolon, 0x28 => Quote, 0x29 => Backtick, 0x2a => LeftShift,
+            0x2b => Backslash,
+            0x2c => Z, 0x2d => X, 0x2e => C, 0x2f => V, 0x30 => B, 0x31 => N, 0x32 => M,
+            0x33 => Comma, 0x34 => Period, 0x35 => Slash, 0x36 => RightShift,
+            0x37 => KeypadStar, 0x38 => LeftAlt, 0x39 => Space, 0x3a => CapsLock,
+            0x3b => F1, 0x3c => F2, 0x3d => F3, 0x3e => F4, 0x3f => F5,
+            0x40 => F6, 0x41 => F7, 0x42 => F8, 0x43 => F9, 0x44 => F10,
+            0x45 => NumLock, 0x46 => ScrollLock,
+            0x47 => Keypad7, 0x48 => Keypad8, 0x49 => Keypad9, 0x4a => KeypadMinus,
+            0x4b => Keypad4, 0x4c => Keypad5, 0x4d => Keypad6, 0x4e => KeypadPlus,
+            0x4f => Keypad1, 0x50 => Keypad2, 0x51 => Keypad3, 0x52 => Keypad0,
+            0x53 => KeypadPeriod, 0x57 => F11, 0x58 => F12,
+            0x9c => KeypadEnter, 0x9d => RightControl, 0xb5 => KeypadSlash,
+            0xb7 => PrintScreen, 0xb8 => RightAlt,
+            0xc7 => Home, 0xc8 => UpArrow, 0xc9 => PageUp, 0xcb => LeftArrow,
+            0xcd => RightArrow, 0xcf => End, 0xd0 => DownArrow, 0xd1 => PageDown,
+            0xd2 => Insert, 0xd3 => Delete, 0xdb => LeftGui, 0xdc => RightGui,
+            0xdd => Menu, 0xc5 => Pause,
+            _ => return None
+        };
+        Some(key)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Modifiers {
+    pub shift: bool,
+    pub control: bool,
+    pub alt: bool,
+    pub caps_lock: bool
+}
+
+impl Modifiers {
+    fn to_bits(self) -> u32 {
+        (self.shift as u32) | (self.control as u32) << 1 |
+            (self.alt as u32) << 2 | (self.caps_lock as u32) << 3
+    }
+
+    fn from_bits(bits: u32) -> Modifiers {
+        Modifiers {
+            shift: bits & 1 != 0,
+            control: bits & 2 != 0,
+            alt: bits & 4 != 0,
+            caps_lock: bits & 8 != 0
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub key: KeyCode,
+    pub pressed: bool,
+    // a press that came from the keyboard repeating a key that was already down
+    pub repeat: bool,
+    pub modifiers: Modifiers
+}
+
+impl KeyEvent {
+    // packed so the queue can be made of plain atomics
+    fn to_bits(self) -> u32 {
+        self.key as u32 | (self.pressed as u32) << 8 |
+            (self.repeat as u32) << 9 | self.modifiers.to_bits() << 10
+    }
+
+    fn from_bits(bits: u32) -> Option<KeyEvent> {
+        Some(KeyEvent {
+            key: KeyCode::from_id(bits as u8)?,
+            pressed: bits & (1 << 8) != 0,
+            repeat: bits & (1 << 9) != 0,
+            modifiers: Modifiers::from_bits(bits >> 10)
+        })
+    }
+}
+
+// only the keyboard interrupt pushes and only the game pops
+static QUEUE: RingBuffer<128> = RingBuffer::new();
+
+// indexed by the key id
+static KEY_DOWN: [AtomicBool; 256] = [const { AtomicBool::new(false) }; 256];
+static CAPS_LOCK: AtomicBool = AtomicBool::new(false);
+
+// decoder state, only touched from the interrupt handler
+static EXTENDED: AtomicBool = AtomicBool::new(false);
+static SKIP_BYTES: AtomicU8 = AtomicU8::new(0);
+
+const EXTENDED_PREFIX: u8 = 0xe0;
+const PAUSE_PREFIX: u8 = 0xe1;
+const RELEASE_BIT: u8 = 0x80;
+// the rest of the pause sequence after 0xe1
+const PAUSE_LENGTH: u8 = 5;
+
+// called from the keyboard interrupt with each byte read from the controller
+pub fn handle_scan_code(scan_code: u8) {
+    let skip = SKIP_BYTES.load(Ordering::Relaxed);
+    if skip > 0 {
+        SKIP_BYTES.store(skip - 1, Ordering::Relaxed);
+        return;
+    }
+
+    match scan_code {
+        EXTENDED_PREFIX => {
+            EXTENDED.store(true, Ordering::Relaxed);
+            return;
+        },
+        PAUSE_PREFIX => {
+            // pause only sends a make code, so it is released straight away
+            SKIP_BYTES.store(PAUSE_LENGTH, Ordering::Relaxed);
+            key_changed(KeyCode::Pause, true);
+            key_changed(KeyCode::Pause, false);
+            return;
+        },
+        _ => {}
+    }
+
+    let extended = EXTENDED.swap(false, Ordering::Relaxed);
+    let pressed = scan_code & RELEASE_BIT == 0;
+    let make_code = scan_code & !RELEASE_BIT;
+
+    // print screen and the extended keys with num lock on come wrapped
+    // in a fake shift, it is not a real key
+    if extended && make_code == KeyCode::LeftShift as u8 {
+        return;
+    }
+
+    let id = if extended { make_code | RELEASE_BIT } else { make_code };
+    if let Some(key) = KeyCode::from_id(id) {
+        key_changed(key, pressed);
+    }
+}
+
+fn key_changed(key: KeyCode, pressed: bool) {
+    let was_down = KEY_DOWN[key as usize].swap(pressed, Ordering::Relaxed);
+    if key == KeyCode::CapsLock && pressed && !was_down {
+        CAPS_LOCK.fetch_xor(true, Ordering::Relaxed);
+    }
+
+    // when the queue is full the event is lost,
+    // but the key state above is still right
+    QUEUE.push(KeyEvent {
+        key,
+        pressed,
+        repeat: pressed && was_down,
+        modifiers: modifiers()
+    }.to_bits());
+}
+
+// the next key event that has not been handled yet, oldest first
+pub fn next_event() -> Option<KeyEvent> {
+    QUEUE.pop().and_then(KeyEvent::from_bits)
+}
+
+// takes every event that is waiting
+pub fn events() -> impl Iterator<Item = KeyEvent> {
+    core::iter::from_fn(next_event)
+}
+
+pub fn is_key_down(key: KeyCode) -> bool {
+    KEY_DOWN[key as usize].load(Ordering::Relaxed)
+}
+
+pub fn modifiers() -> Modifiers {
+    use KeyCode::*;
+    Modifiers {
+        shift: is_key_down(LeftShift) || is_key_down(RightShift),
+        control: is_key_down(LeftControl) || is_key_down(RightControl),
+        alt: is_key_down(LeftAlt) || is_key_down(RightAlt),
+        caps_lock: CAPS_LOCK.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x86_64::instructions::interrupts;
+
+    #[test_case]
+    fn scan_codes_become_key_events() {
+        // the real keyboard interrupt is kept out while the codes are fed in
+        let events = interrupts::without_interrupts(|| {
+            while next_event().is_some() {}
+
+            handle_scan_code(0x1e);
+            handle_scan_code(0x9e);
+            handle_scan_code(0xe0);
+            handle_scan_code(0x48);
+            [next_event(), next_event(), next_event()]
+        });
+
+        let [press, release, arrow] = events;
+        let press = press.unwrap();
+        assert_eq!((press.key, press.pressed), (KeyCode::A, true));
+        let release = release.unwrap();
+        assert_eq!((release.key, release.pressed), (KeyCode::A, false));
+        assert_eq!(arrow.unwrap().key, KeyCode::UpArrow);
+        assert!(is_key_down(KeyCode::UpArrow));
+
+        interrupts::without_interrupts(|| {
+            handle_scan_code(0xe0);
+            handle_scan_code(0xc8);
+            while next_event().is_some() {}
+        });
+    }
+}
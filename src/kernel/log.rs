@@ -0,0 +1,73 @@
+// leveled logging to the serial console. anything above MAX_LEVEL is
+// compared against a constant, so the compiler takes the call out entirely
+use core::fmt;
+use super::time::Instant;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+    Error = 1,
+    Warn,
+    Info,
+    Debug,
+    Trace
+}
+
+impl Level {
+    pub fn name(self) -> &'static str {
+        match self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE"
+        }
+    }
+}
+
+// every piece is logged at debug, which is too much for a release build
+#[cfg(debug_assertions)]
+pub const MAX_LEVEL: Level = Level::Debug;
+#[cfg(not(debug_assertions))]
+pub const MAX_LEVEL: Level = Level::Info;
+
+#[doc(hidden)]
+pub fn _log(level: Level, module: &str, args: fmt::Arguments) {
+    // the crate name is the same on every line
+    let module = module.strip_prefix("tetris::").unwrap_or(module);
+    crate::serial_println!("[{:>8} {:<5} {}] {}", Instant::now().as_millis(),
+        level.name(), module, args);
+}
+
+#[macro_export]
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => {
+        if $level <= $crate::kernel::log::MAX_LEVEL {
+            $crate::kernel::log::_log($level, module_path!(), format_args!($($arg)*));
+        }
+    };
+}
+
+#[macro_export]
+macro_rules! error {
+    ($($arg:tt)*) => ($crate::log!($crate::kernel::log::Level::Error, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! warn {
+    ($($arg:tt)*) => ($crate::log!($crate::kernel::log::Level::Warn, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! info {
+    ($($arg:tt)*) => ($crate::log!($crate::kernel::log::Level::Info, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! debug {
+    ($($arg:tt)*) => ($crate::log!($crate::kernel::log::Level::Debug, $($arg)*));
+}
+
+#[macro_export]
+macro_rules! trace {
+    ($($arg:tt)*) => ($crate::log!($crate::kernel::log::Level::Trace, $($arg)*));
+}
@@ -0,0 +1,20 @@
+use x86_64::instructions::port::Port;
+
+// where the isa-debug-exit device is put by the test arguments in Cargo.toml
+const DEBUG_EXIT_PORT: u16 = 0xf4;
+
+// qemu exits with (code << 1) | 1, so these come out as 33 and 35.
+// 33 is set as the success code for bootimage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u32)]
+pub enum QemuExitCode {
+    Success = 0x10,
+    Failed = 0x11
+}
+
+// only does anything when running under qemu with the device attached,
+// on real hardware the write goes nowhere and this returns
+pub fn exit_qemu(exit_code: QemuExitCode) {
+    let mut port: Port<u32> = Port::new(DEBUG_EXIT_PORT);
+    unsafe { port.write(exit_code as u32) };
+}
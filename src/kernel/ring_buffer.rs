@@ -0,0 +1,72 @@
+use core::sync::atomic::{ AtomicU32, AtomicUsize, Ordering };
+
+// a single producer, single consumer ring buffer for passing values out of
+// an interrupt. only the interrupt pushes and only the game pops, so neither
+// side needs a lock. the values are u32 so the slots can be plain atomics,
+// and one slot is always left empty to tell a full buffer from an empty one
+pub struct RingBuffer<const N: usize> {
+    slots: [AtomicU32; N],
+    head: AtomicUsize,
+    tail: AtomicUsize
+}
+
+impl<const N: usize> Default for RingBuffer<N> {
+    fn default() -> RingBuffer<N> {
+        RingBuffer::new()
+    }
+}
+
+impl<const N: usize> RingBuffer<N> {
+    pub const fn new() -> RingBuffer<N> {
+        RingBuffer {
+            slots: [const { AtomicU32::new(0) }; N],
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0)
+        }
+    }
+
+    // gives back false when the buffer is full and the value was dropped
+    pub fn push(&self, value: u32) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % N;
+        if next == self.head.load(Ordering::Acquire) {
+            return false;
+        }
+
+        self.slots[tail].store(value, Ordering::Relaxed);
+        self.tail.store(next, Ordering::Release);
+        true
+    }
+
+    pub fn pop(&self) -> Option<u32> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let value = self.slots[head].load(Ordering::Relaxed);
+        self.head.store((head + 1) % N, Ordering::Release);
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test_case]
+    fn values_come_out_in_order_and_wrap_around() {
+        let buffer: RingBuffer<4> = RingBuffer::new();
+        for round in 0..3 {
+            assert!(buffer.push(round));
+            assert!(buffer.push(round + 10));
+            assert!(buffer.push(round + 20));
+            assert!(!buffer.push(round + 30));
+
+            assert_eq!(buffer.pop(), Some(round));
+            assert_eq!(buffer.pop(), Some(round + 10));
+            assert_eq!(buffer.pop(), Some(round + 20));
+            assert_eq!(buffer.pop(), None);
+        }
+    }
+}
@@ -0,0 +1,211 @@
+use core::sync::atomic::{ AtomicBool, Ordering };
+use x86_64::instructions::port::Port;
+use spin::Mutex;
+use super::ring_buffer::RingBuffer;
+
+pub const COM1: u16 = 0x3f8;
+pub const COM2: u16 = 0x2f8;
+
+// the uart divides this clock down to the baud rate
+const UART_CLOCK: u32 = 115200;
+
+// register offsets from the base port. the first two are the
+// divisor instead while the divisor latch bit is set
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+const DIVISOR_LATCH: u8 = 0x80;
+// 8 data bits, no parity, one stop bit
+const EIGHT_N_ONE: u8 = 0x03;
+// enabled and cleared, with the receive interrupt after 14 bytes
+const FIFO_ENABLE: u8 = 0xc7;
+// data terminal ready, request to send, and out 2, which
+// has to be set for the interrupt to reach the pic
+const MODEM_READY: u8 = 0x0b;
+const MODEM_LOOPBACK: u8 = 0x1e;
+const RECEIVED_DATA_INTERRUPT: u8 = 0x01;
+
+const DATA_READY: u8 = 0x01;
+const TRANSMIT_EMPTY: u8 = 0x20;
+
+// a 16550 uart, only polled for sending
+pub struct SerialPort {
+    base: u16
+}
+
+impl SerialPort {
+    pub const fn new(base: u16) -> SerialPort {
+        SerialPort { base }
+    }
+
+    fn read(&self, register: u16) -> u8 {
+        let mut port: Port<u8> = Port::new(self.base + register);
+        unsafe { port.read() }
+    }
+
+    fn write(&mut self, register: u16, value: u8) {
+        let mut port: Port<u8> = Port::new(self.base + register);
+        unsafe { port.write(value) }
+    }
+
+    // sets the port up for the baud rate and checks it is really there by
+    // sending a byte to itself. gives back false if there is no uart
+    pub fn init(&mut self, baud: u32, receive_interrupts: bool) -> bool {
+        let divisor = (UART_CLOCK / baud) as u16;
+
+        self.write(INTERRUPT_ENABLE, 0);
+        self.write(LINE_CONTROL, DIVISOR_LATCH);
+        self.write(DATA, divisor as u8);
+        self.write(INTERRUPT_ENABLE, (divisor >> 8) as u8);
+        self.write(LINE_CONTROL, EIGHT_N_ONE);
+        self.write(FIFO_CONTROL, FIFO_ENABLE);
+
+        self.write(MODEM_CONTROL, MODEM_LOOPBACK);
+        self.write(DATA, 0xae);
+        if self.read(DATA) != 0xae {
+            return false;
+        }
+
+        self.write(MODEM_CONTROL, MODEM_READY);
+        if receive_interrupts {
+            self.write(INTERRUPT_ENABLE, RECEIVED_DATA_INTERRUPT);
+        }
+        true
+    }
+
+    pub fn send(&mut self, byte: u8) {
+        while self.read(LINE_STATUS) & TRANSMIT_EMPTY == 0 {
+            core::hint::spin_loop();
+        }
+        self.write(DATA, byte);
+    }
+
+    pub fn try_receive(&mut self) -> Option<u8> {
+        if self.read(LINE_STATUS) & DATA_READY == 0 {
+            return None;
+        }
+        Some(self.read(DATA))
+    }
+}
+
+// bytes received over the link, only the interrupt pushes and only the game pops
+static RECEIVED: RingBuffer<1024> = RingBuffer::new();
+
+// com1 is for text, the tests report through it
+const CONSOLE_PORT: u16 = COM1;
+const CONSOLE_BAUD: u32 = 115200;
+
+static CONSOLE: Mutex<SerialPort> = Mutex::new(SerialPort::new(CONSOLE_PORT));
+
+// the port two machines are linked with for versus
+const LINK_PORT: u16 = COM2;
+const LINK_BAUD: u32 = 38400;
+
+static LINK: Mutex<SerialPort> = Mutex::new(SerialPort::new(LINK_PORT));
+// an empty port reads as all ones, which would look like endless data
+static LINK_PRESENT: AtomicBool = AtomicBool::new(false);
+
+pub fn init() {
+    CONSOLE.lock().init(CONSOLE_BAUD, false);
+    let present = LINK.lock().init(LINK_BAUD, true);
+    LINK_PRESENT.store(present, Ordering::Relaxed);
+    match present {
+        true => crate::info!("link port ready at {} baud", LINK_BAUD),
+        false => crate::warn!("no uart for the link port, versus over serial is off")
+    }
+}
+
+pub fn link_present() -> bool {
+    LINK_PRESENT.load(Ordering::Relaxed)
+}
+
+// called from the link interrupt. it does not take the lock, so a
+// send that was interrupted can not deadlock it
+pub fn handle_link_interrupt() {
+    let mut port = SerialPort::new(LINK_PORT);
+    while let Some(byte) = port.try_receive() {
+        // when the queue is full the byte is lost, the
+        // checksums of the protocol will catch it
+        RECEIVED.push(byte as u32);
+    }
+}
+
+pub fn link_send(bytes: &[u8]) {
+    if !link_present() {
+        return;
+    }
+
+    let mut port = LINK.lock();
+    for byte in bytes {
+        port.send(*byte);
+    }
+}
+
+// the next byte that came in over the link, oldest first
+pub fn link_receive() -> Option<u8> {
+    RECEIVED.pop().map(|byte| byte as u8)
+}
+
+impl core::fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> core::fmt::Result {
+        for byte in s.bytes() {
+            self.send(byte);
+        }
+        Ok(())
+    }
+}
+
+#[doc(hidden)]
+pub fn _print(args: core::fmt::Arguments) {
+    use core::fmt::Write;
+    // no interrupt handler takes this lock, the exception ones write to
+    // com1 without it. so interrupts stay on while it is held, a long
+    // line would otherwise hold back timer ticks and the clock would fall behind
+    CONSOLE.lock().write_fmt(args).unwrap();
+}
+
+// prints to com1, with qemu's -serial stdio it shows up in the terminal
+#[macro_export]
+macro_rules! serial_print {
+    ($($arg:tt)*) => {
+        $crate::kernel::serial::_print(format_args!($($arg)*))
+    };
+}
+
+#[macro_export]
+macro_rules! serial_println {
+    () => ($crate::serial_print!("\n"));
+    ($($arg:tt)*) => ($crate::serial_print!("{}\n", format_args!($($arg)*)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use x86_64::instructions::interrupts::without_interrupts;
+
+    // in loopback the link port gets back what it sends, which then goes
+    // through the same receive path as bytes from the other machine
+    #[test_case]
+    fn link_bytes_come_back_in_loopback() {
+        assert!(link_present());
+
+        // the interrupt would drain the port at the same time as this
+        without_interrupts(|| {
+            LINK.lock().write(MODEM_CONTROL, MODEM_LOOPBACK);
+            link_send(b"tetris");
+            handle_link_interrupt();
+            LINK.lock().write(MODEM_CONTROL, MODEM_READY);
+        });
+
+        let mut received = [0; 6];
+        for byte in received.iter_mut() {
+            *byte = link_receive().unwrap();
+        }
+        assert_eq!(&received, b"tetris");
+        assert_eq!(link_receive(), None);
+    }
+}
@@ -0,0 +1,218 @@
+// the clock the whole kernel runs on, counted by the pit. the game works in
+// milliseconds, so that is what Instant and Duration are measured in
+use core::ops::{ Add, AddAssign, Sub };
+use core::sync::atomic::{ AtomicU64, Ordering };
+use x86_64::instructions::port::Port;
+
+// the rate the pit divides down from
+const PIT_FREQUENCY: u64 = 1193182;
+// fast enough for the game to see every millisecond
+pub const DEFAULT_FREQUENCY: u32 = 1000;
+
+// channel 0, low then high byte of the divisor, square wave mode
+const PIT_COMMAND: u8 = 0x36;
+const COMMAND_PORT: u16 = 0x43;
+const CHANNEL_0_PORT: u16 = 0x40;
+
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+const NANOS_PER_MILLI: u64 = 1_000_000;
+
+static TICKS: AtomicU64 = AtomicU64::new(0);
+// time is added up tick by tick, so changing the rate
+// does not change how long the earlier ticks were
+static ELAPSED_NANOS: AtomicU64 = AtomicU64::new(0);
+static NANOS_PER_TICK: AtomicU64 = AtomicU64::new(0);
+
+pub fn init() {
+    set_frequency(DEFAULT_FREQUENCY);
+}
+
+// programs the pit to interrupt about this many times a second. the
+// divisor is a whole number, so it gives back the rate it really got
+pub fn set_frequency(hz: u32) -> u32 {
+    // a divisor of 0 is taken as 65536, the slowest it can go, about 18.2 hz
+    let divisor = (PIT_FREQUENCY / hz.max(1) as u64).clamp(1, 65536);
+    NANOS_PER_TICK.store(divisor * NANOS_PER_SECOND / PIT_FREQUENCY, Ordering::Relaxed);
+
+    let mut command: Port<u8> = Port::new(COMMAND_PORT);
+    let mut channel_0: Port<u8> = Port::new(CHANNEL_0_PORT);
+    x86_64::instructions::interrupts::without_interrupts(|| unsafe {
+        command.write(PIT_COMMAND);
+        channel_0.write(divisor as u8);
+        channel_0.write((divisor >> 8) as u8);
+    });
+
+    (PIT_FREQUENCY / divisor) as u32
+}
+
+// called from the timer interrupt, once per tick
+pub fn tick() {
+    TICKS.fetch_add(1, Ordering::Relaxed);
+    ELAPSED_NANOS.fetch_add(NANOS_PER_TICK.load(Ordering::Relaxed), Ordering::Relaxed);
+}
+
+pub fn ticks() -> u64 {
+    TICKS.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Duration {
+    millis: u64
+}
+
+impl Duration {
+    pub const ZERO: Duration = Duration { millis: 0 };
+
+    pub const fn from_millis(millis: u64) -> Duration {
+        Duration { millis }
+    }
+
+    pub const fn from_secs(secs: u64) -> Duration {
+        Duration { millis: secs * 1000 }
+    }
+
+    pub const fn as_millis(self) -> u64 {
+        self.millis
+    }
+
+    pub fn saturating_sub(self, other: Duration) -> Duration {
+        Duration { millis: self.millis.saturating_sub(other.millis) }
+    }
+}
+
+impl Add for Duration {
+    type Output = Duration;
+
+    fn add(self, other: Duration) -> Duration {
+        Duration { millis: self.millis + other.millis }
+    }
+}
+
+impl AddAssign for Duration {
+    fn add_assign(&mut self, other: Duration) {
+        self.millis += other.millis;
+    }
+}
+
+// every subtraction stops at zero rather than panicking, the same as
+// an instant minus a later one
+impl Sub for Duration {
+    type Output = Duration;
+
+    fn sub(self, other: Duration) -> Duration {
+        self.saturating_sub(other)
+    }
+}
+
+// a point in time, as the milliseconds since the timer started
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Instant {
+    millis: u64
+}
+
+impl Instant {
+    pub const BOOT: Instant = Instant { millis: 0 };
+
+    pub fn now() -> Instant {
+        Instant { millis: ELAPSED_NANOS.load(Ordering::Relaxed) / NANOS_PER_MILLI }
+    }
+
+    pub const fn from_millis(millis: u64) -> Instant {
+        Instant { millis }
+    }
+
+    pub const fn as_millis(self) -> u64 {
+        self.millis
+    }
+
+    // zero if the other one is later
+    pub fn duration_since(self, earlier: Instant) -> Duration {
+        Duration { millis: self.millis.saturating_sub(earlier.millis) }
+    }
+
+    pub fn elapsed(self) -> Duration {
+        Instant::now().duration_since(self)
+    }
+}
+
+impl Add<Duration> for Instant {
+    type Output = Instant;
+
+    fn add(self, duration: Duration) -> Instant {
+        Instant { millis: self.millis + duration.millis }
+    }
+}
+
+impl Sub<Duration> for Instant {
+    type Output = Instant;
+
+    // stops at boot
+    fn sub(self, duration: Duration) -> Instant {
+        Instant { millis: self.millis.saturating_sub(duration.millis) }
+    }
+}
+
+impl Sub for Instant {
+    type Output = Duration;
+
+    fn sub(self, earlier: Instant) -> Duration {
+        self.duration_since(earlier)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn wait_ticks(count: u64) {
+        let start = ticks();
+        while ticks() < start + count {
+            x86_64::instructions::hlt();
+        }
+    }
+
+    #[test_case]
+    fn the_timer_ticks() {
+        let start = ticks();
+        // a few hundred milliseconds at most
+        for _ in 0..100 {
+            x86_64::instructions::hlt();
+        }
+        assert!(ticks() > start);
+    }
+
+    #[test_case]
+    fn the_clock_is_in_milliseconds() {
+        let start = Instant::now();
+        wait_ticks(100);
+        let elapsed = start.elapsed().as_millis();
+        // 100 ticks of 1193 / 1193182 seconds, give or take the tick it started in
+        assert!((98..=101).contains(&elapsed));
+    }
+
+    #[test_case]
+    fn the_rate_can_change() {
+        assert_eq!(set_frequency(100), 100);
+        let start = Instant::now();
+        wait_ticks(10);
+        assert!(start.elapsed().as_millis() >= 90);
+        assert_eq!(set_frequency(DEFAULT_FREQUENCY), 1000);
+
+        // too slow for the pit, it goes as slow as it can
+        assert_eq!(set_frequency(1), 18);
+        set_frequency(DEFAULT_FREQUENCY);
+    }
+
+    #[test_case]
+    fn durations_and_instants_add_up() {
+        let start = Instant::from_millis(500);
+        let later = start + Duration::from_secs(2);
+        assert_eq!(later.as_millis(), 2500);
+        assert_eq!(later - start, Duration::from_millis(2000));
+        // going backwards stops at zero
+        assert_eq!(start - later, Duration::ZERO);
+        assert_eq!(later - Duration::from_millis(500), Instant::from_millis(2000));
+        assert_eq!(start - Duration::from_secs(1), Instant::BOOT);
+        assert_eq!(Duration::from_millis(5) - Duration::from_millis(9), Duration::ZERO);
+    }
+}
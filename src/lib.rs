@@ -1,17 +1,48 @@
 #![allow(dead_code)]
 #![no_std]
+#![cfg_attr(test, no_main)]
 #![feature(abi_x86_interrupt)]
+#![feature(custom_test_frameworks)]
+#![test_runner(crate::testing::test_runner)]
+#![reexport_test_harness_main = "test_main"]
 
 pub mod kernel {
     pub mod graphics;  
+    pub mod gdt;
+    pub mod exceptions;
     pub mod interrupts;
+    pub mod keyboard;
+    pub mod ring_buffer;
+    pub mod entropy;
+    pub mod cmos;
+    pub mod serial;
+    pub mod qemu;
+    pub mod log;
+    pub mod time;
 
     pub fn init() {
+        serial::init();
+        gdt::init();
         interrupts::init();
     }
  
 }
 
 pub mod game;
+pub mod testing;
 
+// the entry point for the tests inside the library itself
+#[cfg(test)]
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    kernel::init();
+    test_main();
+    testing::hlt_loop();
+}
+
+#[cfg(test)]
+#[panic_handler]
+fn panic(info: &core::panic::PanicInfo) -> ! {
+    testing::test_panic_handler(info)
+}
 
@@ -4,7 +4,8 @@
 use core::panic::PanicInfo;
 use tetris::kernel;
 use tetris::kernel::graphics::TTYFrame;
-use tetris::kernel::interrupts;
+use tetris::kernel::time::Instant;
+use tetris::kernel::keyboard;
 use core::fmt::Write;
 
 #[no_mangle]
@@ -13,10 +14,7 @@ pub extern "C" fn _start() -> ! {
     
     let mut frame = TTYFrame::new();
     loop {
-        let current_time = interrupts::current_time();
-        let keyboard_state = interrupts::current_keyboard_state();
-
-        tetris::game::run(current_time, keyboard_state, &mut frame);
+        tetris::game::run(Instant::now(), keyboard::events(), &mut frame);
         frame.flush();        
         frame = TTYFrame::new();
 
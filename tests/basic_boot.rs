@@ -0,0 +1,35 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(tetris::testing::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::panic::PanicInfo;
+use tetris::kernel::graphics::{ self, TTYFrame };
+
+// nothing is set up first, so this checks the parts that
+// are meant to work before kernel::init, like the panic screen
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    test_main();
+    tetris::testing::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    tetris::testing::test_panic_handler(info)
+}
+
+// serial is not tested on its own, the runner reports every test over it
+
+#[test_case]
+fn frame_draws_before_init() {
+    use core::fmt::Write;
+    let mut frame = TTYFrame::new();
+    write!(frame, "some text").unwrap();
+    frame.flush();
+
+    for (x, c) in "some text".bytes().enumerate() {
+        assert_eq!(graphics::screen_char_at(x, 0), c);
+    }
+}
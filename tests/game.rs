@@ -0,0 +1,81 @@
+#![no_std]
+#![no_main]
+#![feature(custom_test_frameworks)]
+#![test_runner(tetris::testing::test_runner)]
+#![reexport_test_harness_main = "test_main"]
+
+use core::panic::PanicInfo;
+use tetris::kernel;
+use tetris::kernel::time::{ Duration, Instant };
+use tetris::kernel::graphics::TTYFrame;
+use tetris::kernel::keyboard::{ KeyCode, KeyEvent, Modifiers };
+use tetris_rules::GameState;
+use tetris_rules::input::{ Action, Input };
+use tetris_rules::modes::Mode;
+use tetris_rules::settings::Settings;
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    kernel::init();
+    test_main();
+    tetris::testing::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    tetris::testing::test_panic_handler(info)
+}
+
+fn press(key: KeyCode) -> KeyEvent {
+    KeyEvent {
+        key,
+        pressed: true,
+        repeat: false,
+        modifiers: Modifiers::default()
+    }
+}
+
+// the rules are tested on the host, this makes sure they
+// behave the same built for the kernel without std
+#[test_case]
+fn rules_run_on_the_kernel_target() {
+    let mut state = GameState::new(Settings::default(), Mode::Sprint, 12, 0);
+    let next = state.previews()[0];
+
+    let mut input = Input::new();
+    input.press(Action::HardDrop);
+    state.update(&input, 0);
+
+    assert_eq!(state.pieces_placed, 1);
+    assert_eq!(state.piece().map(|(block_type, _)| block_type), Some(next));
+}
+
+// plays from the title screen until the stack tops out, going through
+// the menus, the rendering and the game over screen on the way
+#[test_case]
+fn hard_drops_until_game_over() {
+    let mut frame = TTYFrame::new();
+    let start = Instant::now();
+
+    tetris::game::run(start, [press(KeyCode::Enter)].iter().copied(), &mut frame);
+    for i in 0..200 {
+        let events = [press(KeyCode::Space)];
+        tetris::game::run(start + Duration::from_millis(i), events.iter().copied(), &mut frame);
+        frame.flush();
+        frame = TTYFrame::new();
+    }
+
+    // the game over screen is written from the top left of the frame
+    let end = start + Duration::from_millis(200);
+    tetris::game::run(end, core::iter::empty(), &mut frame);
+    assert!(starts_with(&frame, "game over"));
+
+    // back to the title, so the game is left how it started
+    let mut frame = TTYFrame::new();
+    tetris::game::run(end, [press(KeyCode::Escape)].iter().copied(), &mut frame);
+    assert!(starts_with(&frame, "tetris"));
+}
+
+fn starts_with(frame: &TTYFrame, text: &str) -> bool {
+    text.bytes().enumerate().all(|(x, c)| frame.char_at(x, 0) == c)
+}
@@ -0,0 +1,58 @@
+#![no_std]
+#![no_main]
+#![feature(abi_x86_interrupt)]
+
+use core::panic::PanicInfo;
+use lazy_static::lazy_static;
+use tetris::kernel::{ exceptions, gdt, serial };
+use tetris::kernel::qemu::{ exit_qemu, QemuExitCode };
+use tetris::{ serial_print, serial_println };
+use x86_64::structures::idt::{ InterruptDescriptorTable, InterruptStackFrame };
+
+lazy_static! {
+    // the kernel's own exception entries, with only the double fault handler
+    // swapped for one that ends the test. setting the handler leaves the
+    // stack index alone, so if the kernel did not give the double fault its
+    // own stack this would triple fault instead of passing
+    static ref TEST_IDT: InterruptDescriptorTable = {
+        let mut idt = InterruptDescriptorTable::new();
+        exceptions::register(&mut idt);
+        idt.double_fault.set_handler_fn(test_double_fault_handler);
+        idt
+    };
+}
+
+#[no_mangle]
+pub extern "C" fn _start() -> ! {
+    serial::init();
+    serial_print!("stack_overflow::stack_overflow...\t");
+
+    gdt::init();
+    TEST_IDT.load();
+
+    stack_overflow();
+
+    panic!("execution continued after stack overflow");
+}
+
+// the guard page under the stack turns this into a page fault. the cpu
+// can not push the page fault onto the full stack, so it becomes a double fault
+#[allow(unconditional_recursion)]
+fn stack_overflow() {
+    stack_overflow();
+    // stops the recursion being turned into a loop
+    volatile::Volatile::new(0).read();
+}
+
+extern "x86-interrupt" fn test_double_fault_handler(_stack_frame: InterruptStackFrame,
+    _error_code: u64) -> !
+{
+    serial_println!("[ok]");
+    exit_qemu(QemuExitCode::Success);
+    tetris::testing::hlt_loop();
+}
+
+#[panic_handler]
+fn panic(info: &PanicInfo) -> ! {
+    tetris::testing::test_panic_handler(info)
+}